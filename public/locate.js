@@ -0,0 +1,28 @@
+// Progressive enhancement for the coordinates form: offer to fill it in with the browser's
+// location and submit it.
+(function () {
+  var form = document.querySelector("form.locate");
+  if (!form || !("geolocation" in navigator)) {
+    return;
+  }
+
+  var button = document.createElement("button");
+  button.type = "button";
+  button.textContent = "Use my location";
+  button.addEventListener("click", function () {
+    button.disabled = true;
+    navigator.geolocation.getCurrentPosition(
+      function (position) {
+        form.elements.lat.value = position.coords.latitude.toFixed(4);
+        form.elements.lon.value = position.coords.longitude.toFixed(4);
+        form.submit();
+      },
+      function () {
+        button.disabled = false;
+        button.textContent = "Location unavailable";
+      },
+      { maximumAge: 600000 }
+    );
+  });
+  form.appendChild(button);
+})();
@@ -0,0 +1,55 @@
+// Progressive enhancement for the search form: suggest matching locations as the user types.
+// The form still works without this, submitting to the server as usual.
+(function () {
+  var form = document.querySelector("form.search");
+  var list = document.getElementById("locality-suggestions");
+  if (!form || !list || !window.fetch) {
+    return;
+  }
+
+  var input = form.elements.locality;
+  var country = form.elements.country;
+  var timer = null;
+  var latest = 0;
+
+  function update() {
+    var query = input.value.trim();
+    if (query.length < 3 || !country.value) {
+      list.replaceChildren();
+      return;
+    }
+
+    var request = ++latest;
+    var params = new URLSearchParams({ q: query, country: country.value, limit: "5" });
+    fetch("/suggest?" + params.toString())
+      .then(function (response) {
+        return response.ok ? response.json() : [];
+      })
+      .then(function (suggestions) {
+        // Ignore responses to queries that have since been superseded
+        if (request !== latest) {
+          return;
+        }
+        list.replaceChildren.apply(
+          list,
+          suggestions.map(function (suggestion) {
+            var option = document.createElement("option");
+            option.value = suggestion.name;
+            option.label = [suggestion.name, suggestion.state, suggestion.country]
+              .filter(Boolean)
+              .join(", ");
+            return option;
+          })
+        );
+      })
+      .catch(function () {});
+  }
+
+  function schedule() {
+    clearTimeout(timer);
+    timer = setTimeout(update, 300);
+  }
+
+  input.addEventListener("input", schedule);
+  country.addEventListener("change", schedule);
+})();
@@ -0,0 +1,225 @@
+//! Client IP address determination behind reverse proxies
+
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use rocket::http::HeaderMap;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::Deserialize;
+
+use crate::DewpointConfig;
+
+/// The IP address of the client, if it can be determined
+///
+/// Forwarding headers are only believed when added by a trusted proxy (see
+/// `DewpointConfig::trusted_proxies`). The chain of hops in the header the proxies write (see
+/// `DewpointConfig::trusted_proxy_header`) is walked from the right and the first address that
+/// isn't a trusted proxy is the client. Anything to the left of that could have been made up by
+/// the client.
+pub struct ClientIp(pub Option<IpAddr>);
+
+/// The forwarding header that the trusted proxies add to
+///
+/// Only this header is read. A client can send the other one and a proxy that doesn't know about
+/// it will pass it on untouched.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "kebab-case")]
+pub enum ProxyHeader {
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded`
+    Forwarded,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientIp {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let (trusted_proxies, header) = req
+            .rocket()
+            .state::<DewpointConfig>()
+            .map(|config| {
+                (
+                    config.trusted_proxies.as_slice(),
+                    config.trusted_proxy_header,
+                )
+            })
+            .unwrap_or_default();
+        let peer = req.remote().map(|addr| addr.ip());
+        let hops = forwarded_hops(req.headers(), header);
+
+        Outcome::Success(ClientIp(client_ip(peer, &hops, trusted_proxies)))
+    }
+}
+
+/// The addresses that `header` says the request was forwarded for, in order
+fn forwarded_hops(headers: &HeaderMap<'_>, header: ProxyHeader) -> Vec<Option<IpAddr>> {
+    match header {
+        ProxyHeader::XForwardedFor => headers
+            .get("X-Forwarded-For")
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().parse().ok())
+            .collect(),
+        ProxyHeader::Forwarded => headers
+            .get("Forwarded")
+            .flat_map(|value| value.split(','))
+            .map(forwarded_for)
+            .collect(),
+    }
+}
+
+/// Find the client address given the address of the `peer` that connected to us and the `hops`
+/// it claims the request was forwarded through, in order
+///
+/// Hops are `None` if their address is obfuscated, unknown, or invalid.
+fn client_ip(
+    peer: Option<IpAddr>,
+    hops: &[Option<IpAddr>],
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    let mut client = peer?;
+    for hop in hops.iter().rev() {
+        if !trusted(&client) {
+            break;
+        }
+        // A trusted proxy that doesn't know who it forwarded for leaves us none the wiser
+        client = (*hop)?;
+    }
+    Some(client)
+}
+
+/// Extract the address from the `for` parameter of a `Forwarded` header element, such as
+/// `for=192.0.2.60;proto=http;by=203.0.113.43`
+///
+/// <https://www.rfc-editor.org/rfc/rfc7239#section-4>
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let node = element.split(';').find_map(|pair| {
+        let (name, value) = pair.trim().split_once('=')?;
+        name.eq_ignore_ascii_case("for").then(|| value.trim())
+    })?;
+    let node = node.trim_matches('"');
+
+    // IPv6 addresses are bracketed, optionally followed by a port: "[2001:db8:cafe::17]:4711"
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+    // IPv4 with an optional port: "192.0.2.43:47011"
+    let ip = node.split(':').next()?;
+    ip.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::http::Header;
+
+    const PROXY: &str = "10.0.0.1";
+    const CLIENT: &str = "203.0.113.7";
+    const FORGED: &str = "1.2.3.4";
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn resolve(header: ProxyHeader, peer: &str, headers: &[(&'static str, String)]) -> IpAddr {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.add(Header::new(*name, value.clone()));
+        }
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        let hops = forwarded_hops(&map, header);
+        client_ip(Some(ip(peer)), &hops, &trusted).unwrap()
+    }
+
+    #[test]
+    fn forged_forwarded_next_to_real_x_forwarded_for() {
+        let headers = [
+            ("Forwarded", format!("for={}", FORGED)),
+            ("X-Forwarded-For", CLIENT.to_string()),
+        ];
+        assert_eq!(
+            resolve(ProxyHeader::XForwardedFor, PROXY, &headers),
+            ip(CLIENT)
+        );
+    }
+
+    #[test]
+    fn forged_x_forwarded_for_next_to_real_forwarded() {
+        let headers = [
+            ("X-Forwarded-For", FORGED.to_string()),
+            ("Forwarded", format!("for=\"{}:4711\"", CLIENT)),
+        ];
+        assert_eq!(resolve(ProxyHeader::Forwarded, PROXY, &headers), ip(CLIENT));
+    }
+
+    #[test]
+    fn forged_leftmost_x_forwarded_for() {
+        let headers = [("X-Forwarded-For", format!("{}, {}", FORGED, CLIENT))];
+        assert_eq!(
+            resolve(ProxyHeader::XForwardedFor, PROXY, &headers),
+            ip(CLIENT)
+        );
+    }
+
+    #[test]
+    fn forged_leftmost_forwarded() {
+        let headers = [(
+            "Forwarded",
+            format!("for={}, for={};proto=https", FORGED, CLIENT),
+        )];
+        assert_eq!(resolve(ProxyHeader::Forwarded, PROXY, &headers), ip(CLIENT));
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        let headers = [("X-Forwarded-For", FORGED.to_string())];
+        assert_eq!(
+            resolve(ProxyHeader::XForwardedFor, CLIENT, &headers),
+            ip(CLIENT)
+        );
+    }
+
+    #[test]
+    fn all_trusted_chain() {
+        let headers = [("X-Forwarded-For", "10.1.1.1, 10.2.2.2".to_string())];
+        assert_eq!(
+            resolve(ProxyHeader::XForwardedFor, PROXY, &headers),
+            ip("10.1.1.1")
+        );
+    }
+
+    #[test]
+    fn chain_of_proxies() {
+        let headers = [(
+            "X-Forwarded-For",
+            format!("{}, {}, 10.2.2.2", FORGED, CLIENT),
+        )];
+        assert_eq!(
+            resolve(ProxyHeader::XForwardedFor, PROXY, &headers),
+            ip(CLIENT)
+        );
+    }
+
+    #[test]
+    fn forwarded_ipv6() {
+        assert_eq!(
+            forwarded_for("for=\"[2001:db8:cafe::17]:4711\""),
+            Some(ip("2001:db8:cafe::17"))
+        );
+        assert_eq!(forwarded_for("for=unknown"), None);
+        assert_eq!(forwarded_for("for=_hidden"), None);
+        assert_eq!(forwarded_for("proto=https"), None);
+    }
+
+    #[test]
+    fn unknown_hop_stops_the_walk() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        assert_eq!(client_ip(Some(ip(PROXY)), &[None], &trusted), None);
+    }
+}
@@ -1,7 +1,55 @@
+use std::path::PathBuf;
+
+use ipnet::IpNet;
 use rocket::serde::Deserialize;
 
+use crate::client_ip::ProxyHeader;
+use crate::geoip::GeoIpBackendKind;
+
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct DewpointConfig {
     pub openweather_api_key: String,
+    /// Maximum difference (in degrees) between the upstream and computed dew point before a
+    /// warning is logged
+    #[serde(default = "default_dew_point_tolerance")]
+    pub dew_point_tolerance: f32,
+    /// Number of search results to show, up to 5
+    #[serde(default = "default_location_results")]
+    pub location_results: u8,
+    /// Kind of IP geolocation database at `geoip_path`
+    #[serde(default)]
+    pub geoip_backend: GeoIpBackendKind,
+    /// Maximum number of locations that can be compared at once
+    #[serde(default = "default_compare_limit")]
+    pub compare_limit: usize,
+    /// Path to an IP geolocation database, used to guess the user's country and location
+    #[serde(alias = "ip2location_path")]
+    pub geoip_path: Option<PathBuf>,
+    /// Path to a GeoNames cities file used to search for places without calling OpenWeather
+    pub gazetteer_path: Option<String>,
+    /// Path to the GeoNames `admin1CodesASCII.txt` file, for the states of gazetteer places
+    pub gazetteer_admin1_path: Option<String>,
+    /// Networks of reverse proxies whose forwarding headers are believed
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+    /// The forwarding header written by the trusted proxies
+    #[serde(default)]
+    pub trusted_proxy_header: ProxyHeader,
+    /// Scheme, host and port the site is served from, e.g. `https://dewpoint.7bit.org`, which
+    /// form submissions must come from. The `Host` header is used when unset, but reverse proxies
+    /// may rewrite it.
+    pub public_origin: Option<String>,
+}
+
+fn default_dew_point_tolerance() -> f32 {
+    1.0
+}
+
+fn default_location_results() -> u8 {
+    3
+}
+
+fn default_compare_limit() -> usize {
+    6
 }
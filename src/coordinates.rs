@@ -0,0 +1,322 @@
+//! Parsing of coordinates entered in the search field
+//!
+//! Accepts decimal degrees (`-27.47, 153.02`, `27.47S 153.02E`) and degrees, minutes, seconds
+//! (`27°28′S 153°01′E`, `27 28 12 S, 153 1 30 E`).
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::weather::Metres;
+
+const HEMISPHERES: [char; 4] = ['N', 'S', 'E', 'W'];
+/// Mean radius of the Earth in metres
+const EARTH_RADIUS: f64 = 6_371_008.8;
+/// Decimal places kept in forecast URLs, about 11m at the equator
+const PRECISION: i32 = 4;
+
+/// A point on the Earth's surface in decimal degrees
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Coordinates {
+    pub lat: f32,
+    pub lon: f32,
+}
+
+/// A latitude or longitude with its hemisphere, if one was given
+struct Component {
+    degrees: f64,
+    hemisphere: Option<char>,
+}
+
+impl Coordinates {
+    /// Coordinates from a latitude and longitude, if they're finite and within range
+    pub fn new(lat: f32, lon: f32) -> Option<Coordinates> {
+        ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+            .then_some(Coordinates { lat, lon })
+    }
+
+    /// These coordinates rounded to the precision used in forecast URLs
+    ///
+    /// Nearby points share a URL and a cached forecast this way.
+    pub fn rounded(&self) -> Coordinates {
+        let scale = 10_f32.powi(PRECISION);
+        Coordinates {
+            lat: (self.lat * scale).round() / scale,
+            lon: (self.lon * scale).round() / scale,
+        }
+    }
+
+    /// Great-circle distance to `other`, using the haversine formula
+    pub fn distance_to(&self, other: &Coordinates) -> Metres {
+        let (lat1, lat2) = (
+            f64::from(self.lat).to_radians(),
+            f64::from(other.lat).to_radians(),
+        );
+        let dlat = lat2 - lat1;
+        let dlon = f64::from(other.lon - self.lon).to_radians();
+        let a = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+        let distance = 2. * EARTH_RADIUS * a.sqrt().asin();
+        Metres::new(distance as f32)
+    }
+}
+
+/// Parse `input` as a latitude and longitude pair
+///
+/// Returns `None` if the input isn't coordinates or they are out of range.
+pub fn parse(input: &str) -> Option<Coordinates> {
+    let input = normalise(input);
+    let (first, second) = split(&input)?;
+    let first = parse_component(first)?;
+    let second = parse_component(second)?;
+
+    let (lat, lon) = match (first.hemisphere, second.hemisphere) {
+        (Some('E'), Some('N')) | (Some('E'), Some('S')) => (second, first),
+        (Some('W'), Some('N')) | (Some('W'), Some('S')) => (second, first),
+        (None, None) | (Some('N'), Some('E')) | (Some('N'), Some('W')) => (first, second),
+        (Some('S'), Some('E')) | (Some('S'), Some('W')) => (first, second),
+        _ => return None,
+    };
+
+    if !(-90.0..=90.0).contains(&lat.degrees) || !(-180.0..=180.0).contains(&lon.degrees) {
+        return None;
+    }
+
+    Some(Coordinates {
+        lat: lat.degrees as f32,
+        lon: lon.degrees as f32,
+    })
+}
+
+/// Map the various prime and degree symbols onto ASCII equivalents and uppercase the hemispheres
+fn normalise(input: &str) -> String {
+    input
+        .trim()
+        .replace("''", "\"")
+        .chars()
+        .map(|c| match c {
+            '′' | '’' | '‘' | '`' | '´' => '\'',
+            '″' | '”' | '“' => '"',
+            'º' | '˚' => '°',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect()
+}
+
+/// Split the input into latitude and longitude parts
+fn split(input: &str) -> Option<(&str, &str)> {
+    // An explicit separator
+    if let Some((first, second)) = input.split_once(',') {
+        return if second.contains(',') {
+            None
+        } else {
+            Some((first.trim(), second.trim()))
+        };
+    }
+
+    // After the first hemisphere letter
+    if let Some(index) = input.find(HEMISPHERES) {
+        let (first, second) = input.split_at(index + 1);
+        let second = second.trim();
+        // A leading hemisphere belongs to the first part
+        if first.trim().len() == 1 {
+            let index = second.find(HEMISPHERES)?;
+            // `second` is a suffix of `input`, so this is where the second part starts
+            let second = &second[index..];
+            let end = input.len() - second.len();
+            return Some((&input[..end], second));
+        }
+        return Some((first.trim(), second));
+    }
+
+    // Two plain decimal numbers separated by whitespace. Whole numbers aren't accepted, as postcodes
+    // such as "010 01" (Slovakia) look the same.
+    let mut parts = input.split_whitespace();
+    let first = parts.next()?;
+    let second = parts.next()?;
+    if parts.next().is_some() || !input.contains(['.', '°']) {
+        return None;
+    }
+    Some((first, second))
+}
+
+fn parse_component(input: &str) -> Option<Component> {
+    let input = input.trim();
+    let (hemisphere, input) = match (input.chars().next(), input.chars().last()) {
+        (Some(c @ ('N' | 'S' | 'E' | 'W')), _) => (Some(c), &input[1..]),
+        (_, Some(c @ ('N' | 'S' | 'E' | 'W'))) => (Some(c), &input[..input.len() - 1]),
+        _ => (None, input),
+    };
+
+    let mut numbers = input
+        .split(|c: char| c.is_whitespace() || matches!(c, '°' | '\'' | '"'))
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f64>().ok().filter(|number| number.is_finite()));
+    let degrees = numbers.next()??;
+    let minutes = numbers.next().unwrap_or(Some(0.))?;
+    let seconds = numbers.next().unwrap_or(Some(0.))?;
+    if numbers.next().is_some() || !(0.0..60.).contains(&minutes) || !(0.0..60.).contains(&seconds)
+    {
+        return None;
+    }
+
+    let negative = degrees.is_sign_negative();
+    let magnitude = degrees.abs() + minutes / 60. + seconds / 3600.;
+    let degrees = match hemisphere {
+        Some('S') | Some('W') if negative => return None,
+        Some('S') | Some('W') => -magnitude,
+        _ if negative => -magnitude,
+        _ => magnitude,
+    };
+
+    Some(Component {
+        degrees,
+        hemisphere,
+    })
+}
+
+impl Display for Coordinates {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ns = if self.lat < 0. { 'S' } else { 'N' };
+        let ew = if self.lon < 0. { 'W' } else { 'E' };
+        write!(
+            f,
+            "{:.4}°{} {:.4}°{}",
+            self.lat.abs(),
+            ns,
+            self.lon.abs(),
+            ew
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_parses(input: &str, lat: f32, lon: f32) {
+        let coordinates = parse(input).unwrap_or_else(|| panic!("{:?} didn't parse", input));
+        assert!(
+            (coordinates.lat - lat).abs() < 1e-4 && (coordinates.lon - lon).abs() < 1e-4,
+            "{:?} parsed as {}, {}",
+            input,
+            coordinates.lat,
+            coordinates.lon
+        );
+    }
+
+    #[test]
+    fn signed_decimal_degrees() {
+        assert_parses("-27.47, 153.02", -27.47, 153.02);
+        assert_parses("-27.47,153.02", -27.47, 153.02);
+        assert_parses("51.5 -0.12", 51.5, -0.12);
+        assert_parses("-27 153.5", -27., 153.5);
+        assert_parses("-27° 153°", -27., 153.);
+        assert_parses("+40.7, -74", 40.7, -74.);
+        assert_parses("0, 0", 0., 0.);
+    }
+
+    #[test]
+    fn hemispheres() {
+        assert_parses("27.47S 153.02E", -27.47, 153.02);
+        assert_parses("27.47s, 153.02e", -27.47, 153.02);
+        assert_parses("153.02E 27.47S", -27.47, 153.02);
+        assert_parses("S27.47 E153.02", -27.47, 153.02);
+        assert_parses("40.7N 74W", 40.7, -74.);
+    }
+
+    #[test]
+    fn degrees_minutes_seconds() {
+        assert_parses("27°28′S 153°01′E", -(27. + 28. / 60.), 153. + 1. / 60.);
+        assert_parses("27 28 12 S, 153 1 30 E", -27.47, 153.025);
+        assert_parses("27°28'12\"S 153°1'30\"E", -27.47, 153.025);
+        assert_parses("27°28'12''S 153°1'30''E", -27.47, 153.025);
+        assert_parses("27º 28′ 12″ S 153º 1′ 30″ E", -27.47, 153.025);
+    }
+
+    #[test]
+    fn display_parses() {
+        let brisbane = Coordinates::new(-27.47, 153.02).unwrap();
+        assert_parses(&brisbane.to_string(), -27.47, 153.02);
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_parses("  -27.47 ,   153.02  ", -27.47, 153.02);
+        assert_parses("-27.47 \t 153.02", -27.47, 153.02);
+        assert_parses("N  27.5 E 153", 27.5, 153.);
+        assert_parses("S   27.5   E   153", -27.5, 153.);
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert!(parse("91, 0").is_none());
+        assert!(parse("-90.5, 0").is_none());
+        assert!(parse("0, 180.5").is_none());
+        assert!(parse("91N 0E").is_none());
+        assert!(parse("27 60 0 S, 153 0 0 E").is_none());
+        assert!(parse("27 0 60 S, 153 0 0 E").is_none());
+        assert_parses("90, -180", 90., -180.);
+    }
+
+    #[test]
+    fn conflicting_signs_and_hemispheres() {
+        assert!(parse("-27.47S 153.02E").is_none());
+        assert!(parse("27.47N 27.47S").is_none());
+        assert!(parse("153E 27E").is_none());
+        assert!(parse("27N, 153").is_none());
+    }
+
+    #[test]
+    fn garbage() {
+        for input in [
+            "",
+            "Brisbane",
+            "4000",
+            "27.47",
+            "1, 2, 3",
+            "1 2 3",
+            "010 01",
+            "27 153",
+            "NaN, 0",
+            "inf, 0",
+            "abc, def",
+            "N",
+            "N E",
+            "27°28′S",
+        ] {
+            assert!(parse(input).is_none(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn rounding() {
+        let coordinates = Coordinates {
+            lat: -27.467_94,
+            lon: 153.028_13,
+        };
+        let rounded = coordinates.rounded();
+        assert_eq!((rounded.lat, rounded.lon), (-27.4679, 153.0281));
+        assert_eq!(rounded.rounded(), rounded);
+    }
+
+    #[test]
+    fn validation() {
+        assert!(Coordinates::new(-27.47, 153.02).is_some());
+        assert!(Coordinates::new(90.1, 0.).is_none());
+        assert!(Coordinates::new(0., -180.1).is_none());
+        assert!(Coordinates::new(f32::NAN, 0.).is_none());
+        assert!(Coordinates::new(0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn distance() {
+        let brisbane = Coordinates {
+            lat: -27.4679,
+            lon: 153.0281,
+        };
+        let sydney = Coordinates {
+            lat: -33.8688,
+            lon: 151.2093,
+        };
+        let km = brisbane.distance_to(&sydney).to_kilometres().to_string();
+        assert_eq!(km, "732.7 km");
+    }
+}
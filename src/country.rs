@@ -1,27 +1,133 @@
+use std::collections::HashMap;
 use std::fmt;
-use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::Locale;
 
 use rocket::data::ToByteUnit;
 use rocket::form::{self, DataField, FromFormField, ValueField};
+use rocket::http::impl_from_uri_param_identity;
+use rocket::http::uri::fmt::{FromUriParam, Path, UriDisplay};
+use rocket::request::FromParam;
 
 use rocket::serde::de::Visitor;
 use rocket::serde::{de, Deserializer};
 
-#[derive(Debug)]
+use crate::locale::{self, Clock};
+use crate::weather::UnitSystem;
+
+/// The country table, see the comment at the top of the file for its format
+const COUNTRIES: &str = include_str!("../data/countries.tsv");
+
+#[derive(Debug, Clone)]
 pub struct Country(pub(crate) celes::Country);
 
 impl Country {
+    /// Look up a country by its ISO 3166-1 alpha-2 code
+    pub fn from_code(code: &str) -> Option<Country> {
+        celes::Country::from_alpha2(code).ok().map(Country)
+    }
+
     pub fn code(&self) -> &str {
         self.0.alpha2
     }
 }
 
+/// A country and the formats customarily used there
+#[derive(Debug)]
+pub struct CountryInfo {
+    /// Name shown in the search form
+    pub name: &'static str,
+    pub country: Country,
+    pub units: UnitSystem,
+    pub clock: Clock,
+    pub locale: Locale,
+}
+
+/// The parsed country table
+struct CountryTable {
+    /// Sorted by name
+    countries: Vec<CountryInfo>,
+    /// Index into `countries`, keyed on the (upper case) ISO 3166-1 alpha-2 code
+    by_code: HashMap<&'static str, usize>,
+}
+
+/// The country table, parsed on first use
+///
+/// Panics if the table is invalid or lists a country twice.
+fn table() -> &'static CountryTable {
+    static TABLE: OnceLock<CountryTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let countries = COUNTRIES
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                parse_info(line).unwrap_or_else(|| {
+                    panic!("invalid country on line {} of countries.tsv", index + 1)
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut by_code = HashMap::with_capacity(countries.len());
+        for (index, info) in countries.iter().enumerate() {
+            if by_code.insert(info.country.0.alpha2, index).is_some() {
+                panic!("{} is listed twice in countries.tsv", info.country.code());
+            }
+        }
+        CountryTable { countries, by_code }
+    })
+}
+
+/// All countries, sorted by name
+///
+/// The table is parsed on first use and panics if it's invalid, so this is called at startup to
+/// catch that.
+pub fn all() -> &'static [CountryInfo] {
+    &table().countries
+}
+
+/// The entry for a country, identified by its ISO 3166-1 alpha-2 code
+pub fn info(code: &str) -> Option<&'static CountryInfo> {
+    let table = table();
+    table
+        .by_code
+        .get(code.to_ascii_uppercase().as_str())
+        .map(|&index| &table.countries[index])
+}
+
+fn parse_info(line: &'static str) -> Option<CountryInfo> {
+    let mut fields = line.split('\t');
+    let info = CountryInfo {
+        name: fields.next()?,
+        country: Country::from_code(fields.next()?)?,
+        units: fields.next()?.parse().ok()?,
+        clock: fields.next()?.parse().ok()?,
+        locale: fields.next()?.parse().ok()?,
+    };
+    fields.next().is_none().then_some(info)
+}
+
+/// The country of the first language in an `Accept-Language` header with a region, e.g. Australia
+/// for `en-AU`
+///
+/// Numeric regions such as `es-419` (Latin America) aren't countries and are skipped.
+pub fn from_accept_language(header: &str) -> Option<Country> {
+    locale::parse_accept_language(header)
+        .into_iter()
+        .find_map(|tag| {
+            // The region follows the language and optional script, e.g. zh-Hant-TW
+            tag.split('-')
+                .skip(1)
+                .find(|subtag| subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+        })
+        .and_then(Country::from_code)
+}
+
 #[rocket::async_trait]
 impl<'r> FromFormField<'r> for Country {
     fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
-        celes::Country::from_str(field.value)
-            .map(Country)
-            .map_err(|_err| form::Error::validation("invalid country code").into())
+        Country::from_code(field.value)
+            .ok_or_else(|| form::Error::validation("invalid country code").into())
     }
 
     async fn from_data(field: DataField<'r, '_>) -> form::Result<'r, Self> {
@@ -33,9 +139,49 @@ impl<'r> FromFormField<'r> for Country {
             Err((None, Some(limit)))?;
         }
 
-        celes::Country::from_str(&bytes)
-            .map(Country)
-            .map_err(|_err| form::Error::validation("invalid country code").into())
+        Country::from_code(&bytes)
+            .ok_or_else(|| form::Error::validation("invalid country code").into())
+    }
+}
+
+impl<'a> FromParam<'a> for Country {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        Country::from_code(param).ok_or(param)
+    }
+}
+
+/// Countries appear in URLs as their lowercase code, e.g. `/forecast/au/...`
+impl UriDisplay<Path> for Country {
+    fn fmt(&self, f: &mut rocket::http::uri::fmt::Formatter<'_, Path>) -> fmt::Result {
+        f.write_value(self.code().to_ascii_lowercase())
+    }
+}
+
+impl_from_uri_param_identity!([Path] Country);
+
+impl<'a> FromUriParam<Path, &'a str> for Country {
+    type Target = &'a str;
+
+    fn from_uri_param(param: &'a str) -> &'a str {
+        param
+    }
+}
+
+impl<'a> FromUriParam<Path, &'a String> for Country {
+    type Target = &'a str;
+
+    fn from_uri_param(param: &'a String) -> &'a str {
+        param
+    }
+}
+
+impl FromUriParam<Path, String> for Country {
+    type Target = String;
+
+    fn from_uri_param(param: String) -> String {
+        param
     }
 }
 
@@ -49,16 +195,104 @@ where
         type Value = Country;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("country code")
+            formatter.write_str("an ISO 3166-1 alpha-2 country code")
         }
 
         fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            Ok(FromStr::from_str(value).map(Country).unwrap())
+            Country::from_code(value)
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
         }
     }
 
     deserializer.deserialize_str(CountryCodeVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::form::Form;
+    use rocket::serde::json;
+    use rocket::serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(crate = "rocket::serde")]
+    struct Coded {
+        #[serde(deserialize_with = "country_from_code")]
+        country: Country,
+    }
+
+    fn decode(code: &str) -> Result<Country, String> {
+        json::from_str::<Coded>(&format!(r#"{{"country": "{}"}}"#, code))
+            .map(|coded| coded.country)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn alpha2_codes_in_either_case() {
+        assert_eq!(decode("AU").unwrap().code(), "AU");
+        assert_eq!(decode("au").unwrap().code(), "AU");
+    }
+
+    #[test]
+    fn unknown_codes_are_rejected() {
+        let err = decode("XK").unwrap_err();
+        assert!(
+            err.contains("an ISO 3166-1 alpha-2 country code"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn table_round_trips() {
+        let countries = all();
+        assert!(!countries.is_empty());
+        for row in countries {
+            assert!(!row.name.is_empty());
+            let code = row.country.code();
+            assert_eq!(Country::from_code(code).unwrap().code(), code);
+            let found = info(code).unwrap();
+            assert_eq!(found.country.code(), code);
+            assert_eq!(found.name, row.name);
+            assert_eq!(info(&code.to_ascii_lowercase()).unwrap().name, row.name);
+        }
+        let mut codes = countries
+            .iter()
+            .map(|row| row.country.code())
+            .collect::<Vec<_>>();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), countries.len());
+        assert!(info("XK").is_none());
+    }
+
+    #[derive(FromForm)]
+    struct CountryForm {
+        country: Country,
+    }
+
+    fn form(code: &str) -> Option<Country> {
+        Form::<CountryForm>::parse(&format!("country={}", code))
+            .ok()
+            .map(|form| form.country)
+    }
+
+    #[test]
+    fn names_and_alpha3_codes_are_rejected() {
+        assert!(decode("Australia").is_err());
+        assert!(decode("AUS").is_err());
+        assert!(form("Australia").is_none());
+        assert!(form("AUS").is_none());
+        assert!(Country::from_param("AUS").is_err());
+    }
+
+    #[test]
+    fn form_fields() {
+        assert_eq!(form("AU").unwrap().code(), "AU");
+        assert_eq!(form("au").unwrap().code(), "AU");
+        assert!(form("XK").is_none());
+    }
+}
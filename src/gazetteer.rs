@@ -0,0 +1,328 @@
+//! Offline place name search
+//!
+//! Loads a GeoNames `cities15000`-style tab separated file so that searches can be answered
+//! without calling the OpenWeather geocoding API. The file format is described at
+//! <https://download.geonames.org/export/dump/readme.txt>.
+//!
+//! States are filled in from the optional `admin1CodesASCII.txt` file. GeoNames keeps translated
+//! names in a separate (and much larger) alternate names file that isn't loaded, so places from the
+//! gazetteer always appear under their GeoNames name rather than being localised.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::country::Country;
+use crate::geocoding::Location;
+use crate::slug::slugify;
+
+// Columns in the GeoNames main table
+const NAME: usize = 1;
+const ASCII_NAME: usize = 2;
+const LATITUDE: usize = 4;
+const LONGITUDE: usize = 5;
+const COUNTRY_CODE: usize = 8;
+const ADMIN1_CODE: usize = 10;
+const POPULATION: usize = 14;
+const ELEVATION: usize = 15;
+const DIGITAL_ELEVATION_MODEL: usize = 16;
+const COLUMNS: usize = 19;
+
+/// In-memory index of places, by country
+pub struct Gazetteer {
+    countries: HashMap<String, Vec<Place>>,
+}
+
+struct Place {
+    /// Folded name used for searching
+    key: String,
+    population: u64,
+    location: Location,
+}
+
+impl Gazetteer {
+    /// A gazetteer without any places, used when no file is configured
+    pub fn empty() -> Self {
+        Gazetteer {
+            countries: HashMap::new(),
+        }
+    }
+
+    /// Load a GeoNames dump from `path`, with state names from the `admin1CodesASCII.txt` file at
+    /// `admin1_path` if there is one
+    ///
+    /// Malformed lines are logged and skipped.
+    pub fn from_file<P: AsRef<Path>>(path: P, admin1_path: Option<P>) -> io::Result<Self> {
+        let states = match admin1_path {
+            Some(admin1_path) => read_states(BufReader::new(File::open(admin1_path)?))?,
+            None => HashMap::new(),
+        };
+        Self::from_reader(BufReader::new(File::open(path)?), &states)
+    }
+
+    fn from_reader<R: BufRead>(reader: R, states: &HashMap<String, String>) -> io::Result<Self> {
+        let mut countries: HashMap<String, Vec<Place>> = HashMap::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let places = match parse_line(&line, states) {
+                Some(places) => places,
+                None => {
+                    warn!("Skipping malformed gazetteer entry on line {}", index + 1);
+                    continue;
+                }
+            };
+            for place in places {
+                countries
+                    .entry(place.location.country.code().to_string())
+                    .or_default()
+                    .push(place);
+            }
+        }
+
+        for places in countries.values_mut() {
+            places.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        Ok(Gazetteer { countries })
+    }
+
+    /// Find up to `limit` places in `country` whose name starts with `query`
+    ///
+    /// Matching ignores case and accents. Exact matches come first, then places are ordered by
+    /// population, largest first.
+    pub fn search(&self, query: &str, country: &Country, limit: usize) -> Vec<Location> {
+        let places = match self.countries.get(country.code()) {
+            Some(places) => places,
+            None => return Vec::new(),
+        };
+        let query = fold(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let start = places.partition_point(|place| place.key < query);
+        let mut matches = places[start..]
+            .iter()
+            .take_while(|place| place.key.starts_with(&query))
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| {
+            (b.key == query)
+                .cmp(&(a.key == query))
+                .then(b.population.cmp(&a.population))
+        });
+
+        let mut locations: Vec<Location> = Vec::with_capacity(limit);
+        for place in matches {
+            if locations.len() == limit {
+                break;
+            }
+            // The name and ASCII name of a place may both match
+            if !locations.iter().any(|location| {
+                location.lat == place.location.lat && location.lon == place.location.lon
+            }) {
+                locations.push(place.location.clone());
+            }
+        }
+        locations
+    }
+
+    /// Places in `country` whose name has the slug `slug`, most populous first
+    pub fn find_slug(&self, slug: &str, country: &Country) -> Vec<Location> {
+        let places = match self.countries.get(country.code()) {
+            Some(places) => places,
+            None => return Vec::new(),
+        };
+        // Slugs and keys are both transliterated and lowercased, so the first word of the slug
+        // starts the key of any place that has it
+        let word = slug.split('-').next().unwrap_or_default();
+        if word.is_empty() {
+            return Vec::new();
+        }
+
+        let start = places.partition_point(|place| place.key.as_str() < word);
+        let mut matches = places[start..]
+            .iter()
+            .take_while(|place| place.key.starts_with(word))
+            .filter(|place| slugify(place.location.name()) == slug)
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|place| Reverse(place.population));
+
+        let mut locations: Vec<Location> = Vec::new();
+        for place in matches {
+            if !locations.iter().any(|location| {
+                location.lat == place.location.lat && location.lon == place.location.lon
+            }) {
+                locations.push(place.location.clone());
+            }
+        }
+        locations
+    }
+}
+
+/// Read GeoNames first-level administrative divisions, keyed on `country.admin1` (e.g. `AU.04`)
+fn read_states<R: BufRead>(reader: R) -> io::Result<HashMap<String, String>> {
+    let mut states = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        let mut fields = line.split('\t');
+        if let (Some(code), Some(name)) = (fields.next(), fields.next()) {
+            states.insert(code.to_string(), name.to_string());
+        }
+    }
+    Ok(states)
+}
+
+/// Parse a line into entries for the name, and the ASCII name if it folds differently
+fn parse_line(line: &str, states: &HashMap<String, String>) -> Option<Vec<Place>> {
+    let fields = line.split('\t').collect::<Vec<_>>();
+    if fields.len() < COLUMNS {
+        return None;
+    }
+
+    let name = fields[NAME];
+    let lat = fields[LATITUDE].parse().ok()?;
+    let lon = fields[LONGITUDE].parse().ok()?;
+    let country = Country::from_code(fields[COUNTRY_CODE])?;
+    let population = fields[POPULATION].parse().unwrap_or(0);
+    // The elevation is often blank, the model value is -9999 where there's no data
+    let elevation = fields[ELEVATION]
+        .parse()
+        .ok()
+        .or_else(|| fields[DIGITAL_ELEVATION_MODEL].parse().ok())
+        .filter(|metres| *metres > -9999.);
+    let state = states
+        .get(&format!("{}.{}", fields[COUNTRY_CODE], fields[ADMIN1_CODE]))
+        .cloned();
+    let location = Location::new(name.to_string(), lat, lon, state, country, elevation);
+
+    let mut places = vec![Place {
+        key: fold(name),
+        population,
+        location: location.clone(),
+    }];
+    let ascii_key = fold(fields[ASCII_NAME]);
+    if !ascii_key.is_empty() && ascii_key != places[0].key {
+        places.push(Place {
+            key: ascii_key,
+            population,
+            location,
+        });
+    }
+    Some(places)
+}
+
+/// Normalise a name for searching: transliterated to ASCII and lowercased
+fn fold(name: &str) -> String {
+    deunicode::deunicode(name.trim()).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(name: &str, ascii: &str, country: &str, admin1: &str, population: u64) -> String {
+        // Places are told apart by their coordinates, so derive them from the population
+        format!(
+            "1\t{}\t{}\t\t-27.46794\t{}\tP\tPPLA\t{}\t\t{}\t\t\t\t{}\t\t28\tAustralia/Brisbane\t2019-12-24",
+            name,
+            ascii,
+            population % 180,
+            country,
+            admin1,
+            population
+        )
+    }
+
+    fn gazetteer(lines: &[String]) -> Gazetteer {
+        let states = read_states("AU.04\tQueensland\tQueensland\t2152274\n".as_bytes()).unwrap();
+        Gazetteer::from_reader(lines.join("\n").as_bytes(), &states).unwrap()
+    }
+
+    fn names(locations: &[Location]) -> Vec<&str> {
+        locations.iter().map(Location::name).collect()
+    }
+
+    #[test]
+    fn prefix_and_accent_insensitive_search() {
+        let gazetteer = gazetteer(&[
+            line("Brisbane", "Brisbane", "AU", "04", 2_000_000),
+            line("Brisbane Water", "Brisbane Water", "AU", "02", 1_000),
+            line("Ōsaka", "Osaka", "JP", "32", 2_700_000),
+        ]);
+        let au = Country::from_code("AU").unwrap();
+        let jp = Country::from_code("JP").unwrap();
+        assert_eq!(
+            names(&gazetteer.search("bris", &au, 5)),
+            ["Brisbane", "Brisbane Water"]
+        );
+        assert_eq!(names(&gazetteer.search("OSAKA", &jp, 5)), ["Ōsaka"]);
+        assert_eq!(names(&gazetteer.search("ōsa", &jp, 5)), ["Ōsaka"]);
+        assert!(gazetteer.search("osaka", &au, 5).is_empty());
+    }
+
+    #[test]
+    fn exact_matches_first() {
+        let gazetteer = gazetteer(&[
+            line("Perthshire", "Perthshire", "AU", "", 5_000_000),
+            line("Perth", "Perth", "AU", "08", 1_000),
+        ]);
+        let au = Country::from_code("AU").unwrap();
+        assert_eq!(
+            names(&gazetteer.search("perth", &au, 5)),
+            ["Perth", "Perthshire"]
+        );
+    }
+
+    #[test]
+    fn states_from_admin1_codes() {
+        let gazetteer = gazetteer(&[
+            line("Brisbane", "Brisbane", "AU", "04", 2_000_000),
+            line("Nowhere", "Nowhere", "AU", "99", 1),
+        ]);
+        let au = Country::from_code("AU").unwrap();
+        let locations = gazetteer.search("brisbane", &au, 1);
+        assert_eq!(locations[0].state(), Some("Queensland"));
+        assert_eq!(
+            locations[0].slug_url().unwrap().to_string(),
+            "/au/queensland/brisbane"
+        );
+        assert_eq!(gazetteer.search("nowhere", &au, 1)[0].state(), None);
+    }
+
+    #[test]
+    fn find_by_slug() {
+        let gazetteer = gazetteer(&[
+            line("São Paulo", "Sao Paulo", "BR", "27", 12_000_000),
+            line("St. John's", "St. John's", "CA", "05", 110_000),
+            line("Saint John", "Saint John", "CA", "04", 70_000),
+        ]);
+        let br = Country::from_code("BR").unwrap();
+        let ca = Country::from_code("CA").unwrap();
+        assert_eq!(names(&gazetteer.find_slug("sao-paulo", &br)), ["São Paulo"]);
+        assert_eq!(
+            names(&gazetteer.find_slug("st-john-s", &ca)),
+            ["St. John's"]
+        );
+        assert!(gazetteer.find_slug("st-john", &ca).is_empty());
+        assert!(gazetteer.find_slug("", &ca).is_empty());
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let gazetteer = gazetteer(&[
+            String::from("not\ta\tplace"),
+            line("Brisbane", "Brisbane", "AU", "04", 2_000_000).replace("-27.46794", "south"),
+            line("Cairns", "Cairns", "AU", "04", 150_000),
+            line("Townsville", "Townsville", "AUS", "04", 180_000),
+        ]);
+        let au = Country::from_code("AU").unwrap();
+        assert!(gazetteer.search("brisbane", &au, 5).is_empty());
+        assert_eq!(names(&gazetteer.search("cairns", &au, 5)), ["Cairns"]);
+        assert!(gazetteer.search("townsville", &au, 5).is_empty());
+    }
+}
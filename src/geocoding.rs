@@ -2,14 +2,25 @@
 //!
 //! <https://openweathermap.org/api/geocoding-api>
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use reqwest::Url;
 use rocket::http::uri::Origin;
+use rocket::serde::json::{self, Value};
 use rocket::serde::Deserialize;
 use rocket::uri;
 
+use crate::coordinates::Coordinates;
 use crate::country::{country_from_code, Country};
-use crate::home::rocket_uri_macro_forecast;
+use crate::home::{rocket_uri_macro_forecast, rocket_uri_macro_place_forecast};
+use crate::slug::slugify;
+use crate::weather::Metres;
+
+/// Maximum number of results the direct geocoding API will return
+pub const MAX_LIMIT: u8 = 5;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct Location {
     /// Name of the found location
@@ -23,23 +34,179 @@ pub struct Location {
     /// Country of the found location
     #[serde(deserialize_with = "country_from_code")]
     pub country: Country,
-    // /// Translated names
-    // #[serde(default)]
-    // local_names: HashMap<String, String>,
+    /// Translated names, keyed on ISO 639-1 language code
+    #[serde(default)]
+    local_names: HashMap<String, String>,
+    /// Elevation in metres, where known (not provided by OpenWeather)
+    #[serde(skip)]
+    elevation: Option<f32>,
+    /// Name to show in place of `name`, see [Location::localised]
+    #[serde(skip)]
+    local_name: Option<String>,
+}
+
+/// Find locations matching a place name
+///
+/// <https://openweathermap.org/api/geocoding-api#direct_name>
+pub async fn direct(
+    locality: &str,
+    country: &Country,
+    limit: u8,
+    api_key: &str,
+) -> Result<Vec<Location>, reqwest::Error> {
+    let url = api_url(
+        "direct",
+        &[
+            ("q", format!("{},{}", locality, country.code())),
+            ("limit", limit.to_string()),
+            ("appid", api_key.to_string()),
+        ],
+    );
+    fetch(url).await
+}
+
+/// Find the location of a postal code
+///
+/// <https://openweathermap.org/api/geocoding-api#direct_zip>
+pub async fn zip(
+    postcode: &str,
+    country: &Country,
+    api_key: &str,
+) -> Result<Location, reqwest::Error> {
+    let url = api_url(
+        "zip",
+        &[
+            ("zip", format!("{},{}", postcode, country.code())),
+            ("appid", api_key.to_string()),
+        ],
+    );
+    reqwest::get(url).await?.error_for_status()?.json().await
+}
+
+/// Find locations near the supplied coordinates
+///
+/// <https://openweathermap.org/api/geocoding-api#reverse>
+pub async fn reverse(lat: f32, lon: f32, api_key: &str) -> Result<Vec<Location>, reqwest::Error> {
+    let url = api_url(
+        "reverse",
+        &[
+            ("lat", lat.to_string()),
+            ("lon", lon.to_string()),
+            ("limit", String::from("1")),
+            ("appid", api_key.to_string()),
+        ],
+    );
+    fetch(url).await
+}
+
+/// The URL of a geocoding API `endpoint`, with `params` escaped so that characters such as `&` and
+/// `#` in what the user typed stay part of the query
+fn api_url(endpoint: &str, params: &[(&str, String)]) -> Url {
+    let base = format!("https://api.openweathermap.org/geo/1.0/{}", endpoint);
+    Url::parse_with_params(&base, params).expect("invalid geocoding URL")
+}
+
+/// Fetch a list of locations, skipping any that can't be decoded (e.g. those in a country celes
+/// doesn't know) rather than failing the whole list
+async fn fetch(url: Url) -> Result<Vec<Location>, reqwest::Error> {
+    let values: Vec<Value> = reqwest::get(url).await?.json().await?;
+    Ok(decode_locations(values))
+}
+
+fn decode_locations(values: Vec<Value>) -> Vec<Location> {
+    values
+        .into_iter()
+        .filter_map(|value| match json::from_value(value) {
+            Ok(location) => Some(location),
+            Err(err) => {
+                warn!("Skipping undecodable location: {}", err);
+                None
+            }
+        })
+        .collect()
 }
 
 impl Location {
-    pub fn url(&self) -> Origin {
+    pub(crate) fn new(
+        name: String,
+        lat: f32,
+        lon: f32,
+        state: Option<String>,
+        country: Country,
+        elevation: Option<f32>,
+    ) -> Self {
+        Location {
+            name,
+            lat,
+            lon,
+            state,
+            country,
+            local_names: HashMap::new(),
+            elevation,
+            local_name: None,
+        }
+    }
+
+    /// A copy of this location named in the first of `languages` (BCP 47 tags, most preferred
+    /// first) that it has a translation for
+    ///
+    /// The name is left as is, which is usually English, if there is no translation. Only the
+    /// displayed name changes, the URL is still made from the original name so it resolves.
+    pub fn localised(&self, languages: &[String]) -> Location {
+        let local_name = languages
+            .iter()
+            .filter_map(|tag| tag.split('-').next())
+            .find_map(|language| self.local_names.get(&language.to_ascii_lowercase()))
+            .cloned();
+        Location {
+            local_name,
+            ..self.clone()
+        }
+    }
+
+    /// The state and name slugs of this location's slug URL, or `None` if the name has no slug
+    pub fn slugs(&self) -> Option<(Option<String>, String)> {
+        let name = slugify(&self.name);
+        if name.is_empty() {
+            return None;
+        }
+        let state = self
+            .state
+            .as_deref()
+            .map(slugify)
+            .filter(|state| !state.is_empty());
+        Some((state, name))
+    }
+
+    /// The slug URL of the forecast for this location, e.g. `/au/queensland/brisbane`
+    ///
+    /// This may lead to another place with the same name, so it's only linked to once it's known
+    /// to lead back here.
+    pub fn slug_url(&self) -> Option<Origin> {
+        let (state, name) = self.slugs()?;
+        let mut path = PathBuf::new();
+        if let Some(state) = state {
+            path.push(state);
+        }
+        path.push(name);
+        let country = self.country.code().to_ascii_lowercase();
+        Some(uri!(place_forecast(country, path)))
+    }
+
+    /// The URL of the forecast for this location's (rounded) coordinates
+    pub fn coordinate_url(&self) -> Origin {
+        let country = self.country.code().to_ascii_lowercase();
+        let coordinates = self.coordinates().rounded();
         uri!(forecast(
-            self.country.code().to_ascii_lowercase(),
-            self.lat,
-            self.lon,
-            &self.name
+            country,
+            coordinates.lat,
+            coordinates.lon,
+            self.name()
         ))
     }
 
     pub fn name(&self) -> &str {
-        &self.name
+        self.local_name.as_deref().unwrap_or(&self.name)
     }
 
     pub fn state(&self) -> Option<&str> {
@@ -49,4 +216,46 @@ impl Location {
     pub fn country_name(&self) -> &str {
         self.country.0.long_name
     }
+
+    pub fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            lat: self.lat,
+            lon: self.lon,
+        }
+    }
+
+    pub fn elevation(&self) -> Option<Metres> {
+        self.elevation.map(Metres::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undecodable_locations_are_skipped() {
+        let values: Vec<Value> = json::from_str(
+            r#"[
+                {"name": "Pristina", "lat": 42.66, "lon": 21.17, "country": "XK"},
+                {"name": "Nowhere", "country": "AU"},
+                {"name": "Brisbane", "lat": -27.47, "lon": 153.02, "state": "Queensland", "country": "AU"}
+            ]"#,
+        )
+        .unwrap();
+        let locations = decode_locations(values);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].name(), "Brisbane");
+        assert_eq!(locations[0].country.code(), "AU");
+    }
+
+    #[test]
+    fn queries_are_escaped() {
+        let url = api_url("direct", &[("q", String::from("Q&A #1,AU"))]);
+        assert_eq!(
+            url.as_str(),
+            "https://api.openweathermap.org/geo/1.0/direct?q=Q%26A+%231%2CAU"
+        );
+        assert_eq!(url.query_pairs().next().unwrap().1, "Q&A #1,AU");
+    }
 }
@@ -0,0 +1,377 @@
+//! Geolocation of client IP addresses
+//!
+//! Supports IP2Location BIN databases and MaxMind DB (e.g. GeoLite2 Country/City) files, chosen by
+//! the `geoip_backend` setting.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+use std::thread;
+use std::time::SystemTime;
+
+use arc_swap::ArcSwapOption;
+use ip2location::LocationDB as GeoDB;
+use maxminddb::geoip2;
+use rocket::serde::Deserialize;
+use rocket::tokio::sync::Mutex;
+use rocket::tokio::task;
+
+use crate::coordinates::Coordinates;
+use crate::country::Country;
+
+/// The kinds of database that can be used
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum GeoIpBackendKind {
+    #[default]
+    Ip2Location,
+    MaxMind,
+}
+
+/// A database mapping IP addresses to locations
+pub trait GeoIpBackend: Send + Sync {
+    fn lookup(&self, ip: IpAddr) -> Option<IpLocation>;
+}
+
+/// The IP geolocation database, if one is available
+///
+/// The database file is reloaded by [Ip2Location::reload_if_changed] when it is modified, so it
+/// should be replaced atomically (e.g. written elsewhere and renamed into place).
+#[derive(Clone)]
+pub struct Ip2Location {
+    backend: Arc<ArcSwapOption<Box<dyn GeoIpBackend>>>,
+    kind: GeoIpBackendKind,
+    path: Option<PathBuf>,
+    modified: Arc<Mutex<Option<SystemTime>>>,
+}
+
+/// What the database knows about an address
+///
+/// Fields are `None` when the database doesn't include them. IP2Location DB1 and GeoLite2 Country
+/// only have the country, IP2Location DB3 adds region and city, and DB5 and GeoLite2 City
+/// coordinates.
+pub struct IpLocation {
+    pub country: Option<Country>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    pub coordinates: Option<Coordinates>,
+}
+
+/// Upper limit on the number of IP2Location database handles
+///
+/// Each handle loads the whole database, so more than a few costs more memory than it saves in
+/// waiting: lookups are quick and rarely find every handle busy. The `geoip_throughput` test
+/// measures the difference in home page throughput.
+const MAX_HANDLES: usize = 4;
+
+/// An IP2Location BIN database
+///
+/// Looking up an address needs exclusive access to a handle on the database, so there's a small
+/// pool of them and lookups don't have to wait on each other.
+struct Ip2LocationBackend {
+    handles: Vec<StdMutex<GeoDB>>,
+    next: AtomicUsize,
+}
+
+/// A MaxMind DB, such as GeoLite2 Country or City
+struct MaxMindBackend {
+    reader: maxminddb::Reader<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum GeoIpError {
+    Ip2Location(ip2location::error::Error),
+    MaxMind(maxminddb::MaxMindDBError),
+}
+
+impl Ip2Location {
+    /// Open the database at `path`
+    ///
+    /// If there's no path, or the database can't be opened, lookups return nothing until a
+    /// database appears at `path`.
+    pub fn new(kind: GeoIpBackendKind, path: Option<PathBuf>) -> Self {
+        let (backend, modified) = match &path {
+            Some(path) => match open(kind, path) {
+                Ok(backend) => (Some(Arc::new(backend)), modified_time(path)),
+                Err(err) => {
+                    warn!("Unable to open IP database {}: {}", path.display(), err);
+                    (None, None)
+                }
+            },
+            None => (None, None),
+        };
+        Ip2Location {
+            backend: Arc::new(ArcSwapOption::new(backend)),
+            kind,
+            path,
+            modified: Arc::new(Mutex::new(modified)),
+        }
+    }
+
+    /// Load the database again if the file has changed since it was last loaded
+    ///
+    /// Lookups continue to use the old database while the new one is loaded, and any still using
+    /// it when it's swapped out finish with it.
+    pub async fn reload_if_changed(&self) {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let mut last_modified = self.modified.lock().await;
+        let modified = modified_time(&path);
+        if modified.is_none() || modified == *last_modified {
+            return;
+        }
+
+        let display = path.display().to_string();
+        let kind = self.kind;
+        match task::spawn_blocking(move || open(kind, &path)).await {
+            Ok(Ok(backend)) => {
+                self.backend.store(Some(Arc::new(backend)));
+                *last_modified = modified;
+                info!("Loaded IP database {}", display);
+            }
+            // Try again next time, the file may still be being written
+            Ok(Err(err)) => warn!("Unable to open IP database {}: {}", display, err),
+            Err(err) => error!("IP database loading task failed: {}", err),
+        }
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<IpLocation> {
+        self.backend.load().as_ref()?.lookup(ip)
+    }
+}
+
+fn open(kind: GeoIpBackendKind, path: &Path) -> Result<Box<dyn GeoIpBackend>, GeoIpError> {
+    Ok(match kind {
+        GeoIpBackendKind::Ip2Location => {
+            let handles = thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(MAX_HANDLES)
+                .min(MAX_HANDLES);
+            Box::new(Ip2LocationBackend::open(path, handles)?)
+        }
+        GeoIpBackendKind::MaxMind => Box::new(MaxMindBackend::open(path)?),
+    })
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl Ip2LocationBackend {
+    fn open(path: &Path, handles: usize) -> Result<Self, ip2location::error::Error> {
+        let handles = (0..handles)
+            .map(|_| GeoDB::from_file(path).map(StdMutex::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Ip2LocationBackend {
+            handles,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl GeoIpBackend for Ip2LocationBackend {
+    fn lookup(&self, ip: IpAddr) -> Option<IpLocation> {
+        // Use the first idle handle, starting from a different one each time to spread the load.
+        // If they're all busy wait for one, lookups are quick.
+        let count = self.handles.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut db = (0..count)
+            .find_map(|offset| self.handles[(start + offset) % count].try_lock().ok())
+            .unwrap_or_else(|| {
+                self.handles[start % count]
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+            });
+
+        let record = db.ip_lookup(ip).ok()?;
+        // Fields that aren't in the database are either missing or "-" depending on the version
+        let known = |value: &str| !value.is_empty() && value != "-";
+        let coordinates = match (record.latitude, record.longitude) {
+            // Databases without coordinates report zero
+            (Some(lat), Some(lon)) if lat != 0. || lon != 0. => Some(Coordinates { lat, lon }),
+            _ => None,
+        };
+        Some(IpLocation {
+            country: record
+                .country
+                .and_then(|country| Country::from_code(&country.short_name)),
+            region: record
+                .region
+                .map(String::from)
+                .filter(|region| known(region)),
+            city: record.city.map(String::from).filter(|city| known(city)),
+            coordinates,
+        })
+    }
+}
+
+impl MaxMindBackend {
+    fn open(path: &Path) -> Result<Self, maxminddb::MaxMindDBError> {
+        Ok(MaxMindBackend {
+            reader: maxminddb::Reader::open_readfile(path)?,
+        })
+    }
+}
+
+impl GeoIpBackend for MaxMindBackend {
+    fn lookup(&self, ip: IpAddr) -> Option<IpLocation> {
+        // Country databases have a subset of the City fields, which are all optional
+        let record = self.reader.lookup::<geoip2::City>(ip).ok()?;
+        let english = |names: Option<BTreeMap<&str, &str>>| {
+            names.and_then(|names| names.get("en").map(|name| name.to_string()))
+        };
+        let coordinates =
+            record
+                .location
+                .and_then(|location| match (location.latitude, location.longitude) {
+                    (Some(lat), Some(lon)) => Some(Coordinates {
+                        lat: lat as f32,
+                        lon: lon as f32,
+                    }),
+                    _ => None,
+                });
+        Some(IpLocation {
+            country: record
+                .country
+                .and_then(|country| country.iso_code)
+                .and_then(Country::from_code),
+            region: record
+                .subdivisions
+                .and_then(|subdivisions| subdivisions.into_iter().next())
+                .and_then(|subdivision| english(subdivision.names)),
+            city: record.city.and_then(|city| english(city.names)),
+            coordinates,
+        })
+    }
+}
+
+impl fmt::Display for GeoIpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoIpError::Ip2Location(err) => write!(f, "{}", err),
+            GeoIpError::MaxMind(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for GeoIpError {}
+
+impl From<ip2location::error::Error> for GeoIpError {
+    fn from(err: ip2location::error::Error) -> Self {
+        GeoIpError::Ip2Location(err)
+    }
+}
+
+impl From<maxminddb::MaxMindDBError> for GeoIpError {
+    fn from(err: maxminddb::MaxMindDBError) -> Self {
+        GeoIpError::MaxMind(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Instant;
+
+    /// Compare home page throughput with a single database handle (as before pooling) against the
+    /// pool
+    ///
+    /// Requests go through a local client to the real handler, which looks up the client's address,
+    /// on a multi-threaded runtime with a worker per CPU and several requests in flight per worker.
+    /// A lookup that finds every handle busy blocks its worker thread until one is free, so the
+    /// figures include the requests held up behind it as well as time spent rendering the page.
+    ///
+    /// Needs an IP2Location BIN file, run with:
+    ///
+    /// ```sh
+    /// DEWPOINT_GEOIP_BENCH=IP2LOCATION-LITE-DB5.BIN \
+    ///     cargo test --release geoip_throughput -- --ignored --nocapture
+    /// ```
+    #[test]
+    #[ignore]
+    fn geoip_throughput() {
+        use rocket::fairing::AdHoc;
+        use rocket::local::asynchronous::Client;
+        use rocket::tokio::runtime;
+
+        use crate::gazetteer::Gazetteer;
+        use crate::{home, Countries, DewpointConfig, GeocodingCache, SlugCache, WeatherCache};
+
+        const CLIENTS_PER_WORKER: usize = 8;
+        const REQUESTS: u32 = 2_000;
+
+        let path = env::var("DEWPOINT_GEOIP_BENCH").expect("DEWPOINT_GEOIP_BENCH isn't set");
+        let workers = thread::available_parallelism().map_or(4, NonZeroUsize::get);
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(workers)
+            .enable_all()
+            .build()
+            .unwrap();
+        for handles in [1, workers.min(MAX_HANDLES)] {
+            let backend = Ip2LocationBackend::open(Path::new(&path), handles).unwrap();
+            let geodb = Ip2Location {
+                backend: Arc::new(ArcSwapOption::new(Some(Arc::new(Box::new(backend))))),
+                kind: GeoIpBackendKind::Ip2Location,
+                path: None,
+                modified: Arc::new(Mutex::new(None)),
+            };
+            let figment = rocket::Config::figment()
+                .merge(("openweather_api_key", "bench"))
+                .merge(("log_level", "off"));
+            let rocket = rocket::custom(figment)
+                .manage(geodb)
+                .manage(Countries::new())
+                .manage(WeatherCache::new(1.))
+                .manage(GeocodingCache::new())
+                .manage(Gazetteer::empty())
+                .manage(SlugCache::new())
+                .attach(AdHoc::config::<DewpointConfig>())
+                .mount("/", home::routes());
+
+            let clients = workers * CLIENTS_PER_WORKER;
+            let elapsed = runtime.block_on(async move {
+                let client = Arc::new(Client::untracked(rocket).await.unwrap());
+                let start = Instant::now();
+                let tasks = (0..clients).map(|index| {
+                    let client = Arc::clone(&client);
+                    rocket::tokio::spawn(async move {
+                        for request in 0..REQUESTS {
+                            let ip =
+                                Ipv4Addr::from(request.wrapping_mul(2_654_435_761) ^ index as u32);
+                            let response = client
+                                .get("/")
+                                .remote(SocketAddr::new(IpAddr::from(ip), 443))
+                                .dispatch()
+                                .await;
+                            assert_eq!(response.status(), rocket::http::Status::Ok);
+                        }
+                    })
+                });
+                for task in tasks.collect::<Vec<_>>() {
+                    task.await.unwrap();
+                }
+                start.elapsed()
+            });
+            let total = f64::from(REQUESTS) * clients as f64;
+            println!(
+                "{} handle(s), {} workers, {} clients: {:.0} requests/s",
+                handles,
+                workers,
+                clients,
+                total / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
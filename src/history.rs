@@ -0,0 +1,362 @@
+//! Recently viewed and favourite forecasts
+//!
+//! Kept in a private (encrypted and signed) cookie so there's no server-side storage.
+
+use std::convert::Infallible;
+
+use rocket::http::uri::{Authority, Origin};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::coordinates::Coordinates;
+use crate::country::Country;
+use crate::home::rocket_uri_macro_forecast;
+use crate::DewpointConfig;
+
+const HISTORY: &str = "history";
+/// Number of recently viewed forecasts remembered
+const RECENT: usize = 5;
+/// Maximum number of favourites, to keep the cookie within browser size limits
+const FAVOURITES: usize = 10;
+/// Maximum length of a place name in characters, also for the cookie size limit
+///
+/// Browsers silently drop cookies over 4KB, which would lose the whole history.
+const NAME_LENGTH: usize = 60;
+
+/// A forecast location, with the parameters of the `forecast` route
+#[derive(Clone, PartialEq, Serialize, Deserialize, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct Place {
+    pub country: String,
+    pub lat: f32,
+    pub lon: f32,
+    pub name: String,
+}
+
+/// A form submission from one of our own pages
+///
+/// Guards the actions that change the history against cross-site request forgery. The history
+/// cookie is `SameSite=Lax`, so it's sent with forged POSTs from other sites' pages. Requests are
+/// refused when the `Origin` header isn't the configured `public_origin`, or, without one, doesn't
+/// match the `Host` header (which reverse proxies may rewrite). Browsers send `Origin` with every
+/// POST, so it's only missing for other clients, which are allowed.
+pub struct SameOrigin;
+
+/// The user's recently viewed and favourite forecasts, most recent first
+#[derive(Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct History {
+    recent: Vec<Place>,
+    favourites: Vec<Place>,
+}
+
+impl Place {
+    pub fn url(&self) -> Origin {
+        let coordinates = self.coordinates();
+        uri!(forecast(
+            &self.country,
+            coordinates.lat,
+            coordinates.lon,
+            &self.name
+        ))
+    }
+
+    /// The coordinates of this place, rounded like those in forecast URLs
+    pub fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            lat: self.lat,
+            lon: self.lon,
+        }
+        .rounded()
+    }
+
+    /// This place with its name shortened to at most [NAME_LENGTH] characters and its coordinates
+    /// rounded
+    fn normalised(mut self) -> Place {
+        if let Some((index, _)) = self.name.char_indices().nth(NAME_LENGTH) {
+            self.name.truncate(index);
+        }
+        let coordinates = self.coordinates();
+        self.lat = coordinates.lat;
+        self.lon = coordinates.lon;
+        self
+    }
+
+    /// Whether this is the same place as `other`, even if one was saved before coordinates were
+    /// rounded
+    fn same_location(&self, other: &Place) -> bool {
+        self.country == other.country && self.coordinates() == other.coordinates()
+    }
+}
+
+impl History {
+    pub fn recent(&self) -> &[Place] {
+        &self.recent
+    }
+
+    pub fn favourites(&self) -> &[Place] {
+        &self.favourites
+    }
+
+    pub fn is_favourite(&self, place: &Place) -> bool {
+        self.favourites
+            .iter()
+            .any(|favourite| favourite.same_location(place))
+    }
+
+    /// Record a visit to the forecast for `place`
+    pub fn visit(&mut self, place: Place) {
+        let place = place.normalised();
+        self.recent.retain(|recent| !recent.same_location(&place));
+        self.recent.insert(0, place);
+        self.recent.truncate(RECENT);
+    }
+
+    /// Add `place` to the favourites, or remove it if it's already one
+    pub fn toggle_favourite(&mut self, place: Place) {
+        let place = place.normalised();
+        if self.is_favourite(&place) {
+            self.favourites
+                .retain(|favourite| !favourite.same_location(&place));
+        } else {
+            self.favourites.insert(0, place);
+            self.favourites.truncate(FAVOURITES);
+        }
+    }
+
+    /// Forget the recently viewed forecasts, favourites are kept
+    pub fn clear_recent(&mut self) {
+        self.recent.clear();
+    }
+
+    /// Store the history in its cookie
+    ///
+    /// The cookie is `SameSite=Lax` rather than `Strict`: forecasts save the history, and a
+    /// `Strict` cookie isn't sent when following a link from another site, so the visit would
+    /// replace the whole history.
+    pub fn save(&self, cookies: &CookieJar<'_>) {
+        match json::to_string(self) {
+            Ok(value) => cookies.add_private(
+                Cookie::build((HISTORY, value))
+                    .same_site(SameSite::Lax)
+                    .permanent(),
+            ),
+            Err(err) => error!("Unable to serialise history: {}", err),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for History {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // An unreadable cookie (e.g. from an older version) starts the history afresh
+        let history = req
+            .cookies()
+            .get_private(HISTORY)
+            .and_then(|cookie| json::from_str(cookie.value()).ok())
+            .unwrap_or_default();
+        Outcome::Success(history)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SameOrigin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        let origin = match headers.get_one("Origin") {
+            Some(origin) => origin,
+            None => return Outcome::Success(SameOrigin),
+        };
+        let public_origin = req
+            .rocket()
+            .state::<DewpointConfig>()
+            .and_then(|config| config.public_origin.as_deref());
+        if is_same_origin(origin, public_origin, headers.get_one("Host")) {
+            Outcome::Success(SameOrigin)
+        } else {
+            warn!("Refusing cross-origin request from {}", origin);
+            Outcome::Error((Status::Forbidden, ()))
+        }
+    }
+}
+
+/// Whether the `Origin` header `origin` is this site: `public_origin` if configured, otherwise the
+/// `Host` header `host`
+fn is_same_origin(origin: &str, public_origin: Option<&str>, host: Option<&str>) -> bool {
+    if let Some(public_origin) = public_origin {
+        return origin.eq_ignore_ascii_case(public_origin.trim_end_matches('/'));
+    }
+
+    // The origin is scheme://host[:port], compared with the Host header
+    let origin_host = origin
+        .split_once("://")
+        .and_then(|(_scheme, authority)| Authority::parse(authority).ok())
+        .map(|authority| authority.to_string());
+    match (origin_host, host) {
+        (Some(origin_host), Some(host)) => origin_host.eq_ignore_ascii_case(host),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(name: &str, lat: f32) -> Place {
+        Place {
+            country: String::from("au"),
+            lat,
+            lon: 153.0281,
+            name: name.to_string(),
+        }
+    }
+
+    #[post("/")]
+    fn guarded(_same_origin: SameOrigin) {}
+
+    fn post_with(headers: &[(&'static str, &'static str)]) -> Status {
+        use rocket::http::Header;
+        use rocket::local::blocking::Client;
+
+        let client = Client::untracked(rocket::build().mount("/", routes![guarded])).unwrap();
+        let mut request = client.post("/");
+        for (name, value) in headers {
+            request = request.header(Header::new(*name, *value));
+        }
+        let status = request.dispatch().status();
+        status
+    }
+
+    #[test]
+    fn same_origin_is_allowed() {
+        let headers = [
+            ("Host", "dewpoint.7bit.org"),
+            ("Origin", "https://dewpoint.7bit.org"),
+        ];
+        assert_eq!(post_with(&headers), Status::Ok);
+        let headers = [
+            ("Host", "localhost:8000"),
+            ("Origin", "http://localhost:8000"),
+        ];
+        assert_eq!(post_with(&headers), Status::Ok);
+    }
+
+    #[test]
+    fn missing_origin_is_allowed() {
+        assert_eq!(post_with(&[("Host", "dewpoint.7bit.org")]), Status::Ok);
+    }
+
+    #[test]
+    fn cross_origin_is_refused() {
+        let headers = [
+            ("Host", "dewpoint.7bit.org"),
+            ("Origin", "https://evil.example"),
+        ];
+        assert_eq!(post_with(&headers), Status::Forbidden);
+        let headers = [("Host", "dewpoint.7bit.org"), ("Origin", "null")];
+        assert_eq!(post_with(&headers), Status::Forbidden);
+    }
+
+    #[test]
+    fn configured_public_origin_is_used() {
+        let public_origin = Some("https://dewpoint.7bit.org/");
+        // The proxy passes on its own address as the Host
+        let host = Some("127.0.0.1:8000");
+        assert!(is_same_origin(
+            "https://dewpoint.7bit.org",
+            public_origin,
+            host
+        ));
+        assert!(!is_same_origin(
+            "http://127.0.0.1:8000",
+            public_origin,
+            host
+        ));
+        assert!(!is_same_origin("https://evil.example", public_origin, host));
+        assert!(!is_same_origin(
+            "http://dewpoint.7bit.org",
+            public_origin,
+            host
+        ));
+    }
+
+    #[get("/visit")]
+    fn visit(cookies: &CookieJar<'_>) {
+        let mut history = History::default();
+        history.visit(place("Brisbane", -27.4679));
+        history.save(cookies);
+    }
+
+    #[test]
+    fn cookie_is_sent_when_arriving_from_other_sites() {
+        use rocket::local::blocking::Client;
+
+        let client = Client::untracked(rocket::build().mount("/", routes![visit])).unwrap();
+        let response = client.get("/visit").dispatch();
+        let cookie = response.cookies().get(HISTORY).unwrap();
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    }
+
+    #[test]
+    fn visits_are_most_recent_first_without_duplicates() {
+        let mut history = History::default();
+        history.visit(place("Brisbane", -27.4679));
+        history.visit(place("Cairns", -16.9203));
+        history.visit(place("Brisbane", -27.4679));
+        let names = history
+            .recent()
+            .iter()
+            .map(|place| place.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Brisbane", "Cairns"]);
+    }
+
+    #[test]
+    fn favourites_toggle() {
+        let mut history = History::default();
+        history.toggle_favourite(place("Brisbane", -27.4679));
+        assert!(history.is_favourite(&place("Renamed", -27.4679)));
+        history.toggle_favourite(place("Brisbane", -27.4679));
+        assert!(history.favourites().is_empty());
+    }
+
+    #[test]
+    fn coordinates_are_rounded() {
+        let mut history = History::default();
+        history.toggle_favourite(place("Brisbane", -27.467_94));
+        assert_eq!(history.favourites()[0].lat, -27.4679);
+        assert!(history.is_favourite(&place("Brisbane", -27.4679)));
+        history.visit(place("Brisbane", -27.4679));
+        history.visit(place("Brisbane", -27.467_94));
+        assert_eq!(history.recent().len(), 1);
+    }
+
+    #[test]
+    fn long_names_are_truncated() {
+        let mut history = History::default();
+        history.visit(place(&"ü".repeat(1000), 0.));
+        assert_eq!(history.recent()[0].name.chars().count(), NAME_LENGTH);
+    }
+
+    #[test]
+    fn full_history_fits_in_a_cookie() {
+        let mut history = History::default();
+        for index in 0..FAVOURITES {
+            let lat = -10. - index as f32 * 1.234_567;
+            history.visit(place(&"ü".repeat(1000), lat));
+            history.toggle_favourite(place(&"ü".repeat(1000), lat));
+        }
+        assert_eq!(history.recent().len(), RECENT);
+        assert_eq!(history.favourites().len(), FAVOURITES);
+        // Private cookies are encrypted and base64 encoded, adding about a third plus a nonce and tag
+        let value = json::to_string(&history).unwrap();
+        let encrypted = (value.len() + 12 + 16) * 4 / 3 + HISTORY.len() + 1;
+        assert!(encrypted < 4096, "cookie would be {} bytes", encrypted);
+    }
+}
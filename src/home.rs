@@ -1,58 +1,127 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::net::IpAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
 
 use askama::Template;
-use rocket::form::Form;
-use rocket::request::FlashMessage;
-use rocket::{Route, State};
+use chrono::NaiveDate;
+use rocket::form::{self, Form};
+use rocket::futures::future;
+use rocket::http::{CookieJar, Header, Status};
+use rocket::request::{FlashMessage, Request};
+use rocket::response::{Flash, Redirect};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{Catcher, Route, State};
 
-use crate::country::Country;
-use crate::geocoding::Location;
-use crate::weather::{OneCall, TemperatureUnit};
-use crate::{Countries, CountryArray, DewpointConfig, Ip2Location, WeatherCache};
+use crate::client_ip::ClientIp;
+use crate::coordinates::{self, Coordinates};
+use crate::country::{Country, CountryInfo};
+use crate::gazetteer::Gazetteer;
+use crate::geocoding::{self, Location};
+use crate::history::{History, Place, SameOrigin};
+use crate::locale::{Clock, DateTimeFormat};
+use crate::postcode;
+use crate::preferences::{
+    self, CountryHints, DateTimePreference, LanguagePreference, UnitPreference,
+};
+use crate::slug::slugify;
+use crate::weather::{Comfort, OneCall, PressureUnit, Timezone, UnitSystem, Units, WindSpeedUnit};
+use crate::{Countries, DewpointConfig, GeocodingCache, Ip2Location, SlugCache, WeatherCache};
 
 // These are to make the compiler rebuild when they change
 // TODO: Check that they don't end up in the final binary
 const _HOME: &[u8] = include_bytes!("../templates/home.html");
 const _FORECAST: &[u8] = include_bytes!("../templates/forecast.html");
 
+pub fn catchers() -> Vec<Catcher> {
+    catchers![error]
+}
+
 pub fn routes() -> Vec<Route> {
-    routes![home, about, acknowledgements, location, forecast, robots]
+    routes![
+        home,
+        about,
+        acknowledgements,
+        location,
+        suggest,
+        locate,
+        forecast,
+        place_forecast,
+        favourite,
+        clear_history,
+        compare,
+        robots
+    ]
 }
 
 #[derive(Template)]
 #[template(path = "home.html")]
 struct HomeContext<'f> {
     title: String,
-    ip_country: String,
-    countries: Arc<CountryArray>,
+    locality: String,
+    selected_country: String,
+    countries: &'static [CountryInfo],
+    /// Forecast for the city the client appears to be in, with the city's name
+    nearby: Option<(String, String)>,
+    history: History,
     flash: Option<FlashMessage<'f>>,
 }
 
-#[get("/")]
+/// The search form, pre-filled with `locality` and `country` when returning from a failed search
+#[allow(clippy::too_many_arguments)]
+#[get("/?<locality>&<country>")]
 async fn home<'f>(
-    client_ip: Option<IpAddr>,
+    locality: Option<String>,
+    country: Option<String>,
+    client_ip: ClientIp,
     flash: Option<FlashMessage<'f>>,
     geodb: &State<Ip2Location>,
     countries: &State<Countries>,
+    country_hints: CountryHints,
+    history: History,
 ) -> HomeContext<'f> {
-    let ip_country = if let Some(ip) = client_ip {
-        let mut geodb = geodb.0.lock().await;
-        geodb
-            .ip_lookup(ip)
-            .ok()
-            .and_then(|record| record.country)
-            .map(|country| country.short_name.to_string())
-    } else {
-        None
-    }
-    .unwrap_or_else(|| String::from("-"));
+    let ip_location = client_ip.0.and_then(|ip| geodb.lookup(ip));
+    let (ip_country, ip_city, nearby) = match ip_location {
+        Some(location) => {
+            let nearby = match (&location.country, &location.city, location.coordinates) {
+                (Some(country), Some(city), Some(coordinates)) => {
+                    let coordinates = coordinates.rounded();
+                    let url = uri!(forecast(
+                        country.code().to_ascii_lowercase(),
+                        coordinates.lat,
+                        coordinates.lon,
+                        city
+                    ));
+                    Some((url.to_string(), city.clone()))
+                }
+                _ => None,
+            };
+            (location.country, location.city, nearby)
+        }
+        None => (None, None, None),
+    };
+
+    // Only suggest the city if the form is for its country
+    let locality = match (locality, &country) {
+        (Some(locality), _) => locality,
+        (None, None) => ip_city.unwrap_or_default(),
+        (None, Some(_)) => String::new(),
+    };
+    let selected_country = country
+        .or_else(|| {
+            country_hints
+                .infer(ip_country)
+                .map(|country| country.code().to_string())
+        })
+        .unwrap_or_else(|| String::from("-"));
 
     HomeContext {
         title: String::from("Home"),
-        ip_country,
-        countries: Arc::clone(&countries.0),
+        locality,
+        selected_country,
+        countries: countries.0,
+        nearby,
+        history,
         flash,
     }
 }
@@ -97,45 +166,357 @@ fn about<'f>(flash: Option<FlashMessage<'f>>) -> AboutContext<'f> {
 }
 
 #[derive(FromForm)]
-struct LocationForm {
+struct LocationForm<'r> {
     locality: String,
     /// ISO 3166-1 alpha-2
     ///
     /// https://en.wikipedia.org/wiki/ISO_3166-1_alpha-2
-    country: Country,
+    ///
+    /// Errors are kept so the user can be sent back to the form rather than getting a 422.
+    country: form::Result<'r, Country>,
 }
 
 #[derive(Template)]
 #[template(path = "locations.html")]
 struct LocationContext<'f> {
     title: String,
-    locations: Vec<Location>,
+    locations: Vec<LocationResult>,
     flash: Option<FlashMessage<'f>>,
 }
 
+/// A search result with the details that help tell similarly named places apart
+struct LocationResult {
+    location: Location,
+    url: String,
+    coordinates: Coordinates,
+    elevation: Option<String>,
+    /// Distance from the client's approximate location
+    distance: Option<String>,
+}
+
+#[derive(Responder)]
+enum LocationResponse<'f> {
+    Locations(LocationContext<'f>),
+    Forecast(Redirect),
+    Retry(Flash<Redirect>),
+}
+
+impl LocationResponse<'_> {
+    /// Send the user back to the search form with `message`, keeping what they entered
+    fn retry(
+        flash: fn(Redirect, &'static str) -> Flash<Redirect>,
+        message: &'static str,
+        locality: &str,
+        country: Option<&Country>,
+    ) -> Self {
+        let country = country.map(Country::code);
+        LocationResponse::Retry(flash(
+            Redirect::to(uri!(home(Some(locality), country))),
+            message,
+        ))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 #[post("/", data = "<form>")]
 async fn location<'f>(
+    client_ip: ClientIp,
     flash: Option<FlashMessage<'f>>,
     config: &State<DewpointConfig>,
-    form: Form<LocationForm>,
-) -> LocationContext<'f> {
-    let url = format!(
-        "http://api.openweathermap.org/geo/1.0/direct?q={city},{country}&limit=3&appid={apikey}",
-        city = form.locality.trim(),
-        country = form.country.code(),
-        apikey = config.openweather_api_key
-    );
-    let locations: Vec<Location> = reqwest::get(url)
-        .await
-        .expect("FIXME")
-        .json()
+    geodb: &State<Ip2Location>,
+    geocoding_cache: &State<GeocodingCache>,
+    gazetteer: &State<Gazetteer>,
+    slug_cache: &State<SlugCache>,
+    language_preference: LanguagePreference,
+    unit_preference: UnitPreference,
+    cookies: &CookieJar<'_>,
+    form: Form<LocationForm<'_>>,
+) -> LocationResponse<'f> {
+    let locality = form.locality.trim();
+    let country = match &form.country {
+        Ok(country) => country,
+        Err(_) => {
+            return LocationResponse::retry(
+                Flash::error,
+                "Please select a country.",
+                locality,
+                None,
+            )
+        }
+    };
+    if locality.is_empty() {
+        return LocationResponse::retry(
+            Flash::error,
+            "Please enter a locality, postcode, or coordinates.",
+            locality,
+            Some(country),
+        );
+    }
+    let api_key = &config.openweather_api_key;
+
+    // Coordinates go straight to the forecast, in the country that determines the units
+    if let Some(coordinates) = coordinates::parse(locality) {
+        let coordinates = coordinates.rounded();
+        let country = coordinates_country(coordinates, &client_ip, geodb, api_key)
+            .await
+            .unwrap_or_else(|| country.clone());
+        return LocationResponse::Forecast(Redirect::to(uri!(forecast(
+            country,
+            coordinates.lat,
+            coordinates.lon,
+            coordinates.to_string()
+        ))));
+    }
+
+    // The country field is pre-filled with a guess, so it's only remembered when used to search
+    // for a place
+    preferences::remember_country(cookies, country);
+
+    let postcode = if postcode::looks_like_postcode(country, locality) {
+        geocoding::zip(locality, country, api_key)
+            .await
+            .map_err(|err| info!("Postcode lookup failed, trying name search: {}", err))
+            .ok()
+    } else {
+        None
+    };
+    let locations = match postcode {
+        Some(location) => vec![location],
+        None => match search(
+            locality,
+            country,
+            usize::from(config.location_results.clamp(1, geocoding::MAX_LIMIT)),
+            gazetteer,
+            geocoding_cache,
+            api_key,
+        )
         .await
-        .expect("FIXME");
+        {
+            Ok(locations) => locations,
+            Err(err) => {
+                error!("Geocoding failed: {}", err);
+                return LocationResponse::retry(
+                    Flash::error,
+                    "Unable to search for locations, please try again later.",
+                    locality,
+                    Some(country),
+                );
+            }
+        },
+    };
+    if locations.is_empty() {
+        return LocationResponse::retry(
+            Flash::warning,
+            "No locations matched your search.",
+            locality,
+            Some(country),
+        );
+    }
+    let mut locations = locations
+        .iter()
+        .map(|location| location.localised(language_preference.languages()))
+        .collect::<Vec<_>>();
+
+    // No need to make the user choose between one location
+    if locations.len() == 1 {
+        let location = locations.remove(0);
+        let url = location_url(&location, gazetteer, geocoding_cache, slug_cache, api_key).await;
+        return LocationResponse::Forecast(Redirect::to(url));
+    }
+
+    let distance_unit = unit_preference.resolve(country.code()).distance();
+    let client_coordinates = client_ip
+        .0
+        .and_then(|ip| geodb.lookup(ip))
+        .and_then(|location| location.coordinates);
+    let mut results = Vec::with_capacity(locations.len());
+    for location in locations {
+        let coordinates = location.coordinates();
+        results.push(LocationResult {
+            url: location_url(&location, gazetteer, geocoding_cache, slug_cache, api_key).await,
+            elevation: location
+                .elevation()
+                .map(|elevation| elevation.display_height(distance_unit)),
+            distance: client_coordinates
+                .map(|client| client.distance_to(&coordinates).display(distance_unit)),
+            coordinates,
+            location,
+        });
+    }
 
-    LocationContext {
+    LocationResponse::Locations(LocationContext {
         title: format!("Locations matching {}", form.locality),
-        locations,
+        locations: results,
         flash,
+    })
+}
+
+/// The country for a forecast at `coordinates`: the country they're in if reverse geocoding finds
+/// a place nearby, otherwise the client's (e.g. for points at sea)
+async fn coordinates_country(
+    coordinates: Coordinates,
+    client_ip: &ClientIp,
+    geodb: &Ip2Location,
+    api_key: &str,
+) -> Option<Country> {
+    let nearby = geocoding::reverse(coordinates.lat, coordinates.lon, api_key)
+        .await
+        .map_err(|err| warn!("Reverse geocoding coordinates failed: {}", err))
+        .ok()
+        .and_then(|locations| locations.into_iter().next());
+    match nearby {
+        Some(location) => Some(location.country),
+        None => client_ip
+            .0
+            .and_then(|ip| geodb.lookup(ip))
+            .and_then(|location| location.country),
+    }
+}
+
+/// A location suggested as the user types in the search field
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Suggestion {
+    name: String,
+    state: Option<String>,
+    country: String,
+    url: String,
+}
+
+/// Suggest locations matching a partially typed locality
+///
+/// Queries shorter than three characters return no suggestions to avoid needless API calls.
+#[allow(clippy::too_many_arguments)]
+#[get("/suggest?<q>&<country>&<limit>")]
+async fn suggest(
+    q: &str,
+    country: Country,
+    limit: Option<u8>,
+    config: &State<DewpointConfig>,
+    geocoding_cache: &State<GeocodingCache>,
+    gazetteer: &State<Gazetteer>,
+    slug_cache: &State<SlugCache>,
+    language_preference: LanguagePreference,
+) -> Json<Vec<Suggestion>> {
+    let q = q.trim();
+    if q.chars().count() < 3 {
+        return Json(Vec::new());
+    }
+
+    let limit = limit
+        .unwrap_or(geocoding::MAX_LIMIT)
+        .clamp(1, geocoding::MAX_LIMIT);
+    let locations = search(
+        q,
+        &country,
+        usize::from(limit),
+        gazetteer,
+        geocoding_cache,
+        &config.openweather_api_key,
+    )
+    .await
+    .unwrap_or_else(|err| {
+        error!("Geocoding suggestions failed: {}", err);
+        Vec::new()
+    });
+    let mut suggestions = Vec::with_capacity(locations.len());
+    for location in &locations {
+        let location = location.localised(language_preference.languages());
+        let url = location_url(
+            &location,
+            gazetteer,
+            geocoding_cache,
+            slug_cache,
+            &config.openweather_api_key,
+        )
+        .await;
+        suggestions.push(Suggestion {
+            name: location.name().to_string(),
+            state: location.state().map(String::from),
+            country: location.country_name().to_string(),
+            url,
+        });
+    }
+    Json(suggestions)
+}
+
+/// Find up to `limit` locations named `locality` in `country`
+///
+/// The local gazetteer is searched first, OpenWeather is only consulted when it has no matches.
+async fn search(
+    locality: &str,
+    country: &Country,
+    limit: usize,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    api_key: &str,
+) -> Result<Vec<Location>, reqwest::Error> {
+    let locations = gazetteer.search(locality, country, limit);
+    if !locations.is_empty() {
+        return Ok(locations);
+    }
+
+    let mut locations = geocoding_cache
+        .get_or_fetch(locality, country, api_key)
+        .await?;
+    locations.truncate(limit);
+    Ok(locations)
+}
+
+#[derive(FromForm)]
+struct CoordinatesForm {
+    lat: f32,
+    lon: f32,
+}
+
+/// Find the named location nearest to the supplied coordinates and redirect to its forecast
+#[post("/locate", data = "<form>")]
+async fn locate(
+    config: &State<DewpointConfig>,
+    geocoding_cache: &State<GeocodingCache>,
+    gazetteer: &State<Gazetteer>,
+    slug_cache: &State<SlugCache>,
+    language_preference: LanguagePreference,
+    form: Form<CoordinatesForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    let coordinates = Coordinates::new(form.lat, form.lon).ok_or_else(|| {
+        Flash::error(
+            Redirect::to(uri!(home(_, _))),
+            "Latitude must be between -90 and 90, longitude between -180 and 180.",
+        )
+    })?;
+
+    let locations = geocoding::reverse(
+        coordinates.lat,
+        coordinates.lon,
+        &config.openweather_api_key,
+    )
+    .await
+    .map_err(|err| {
+        error!("Reverse geocoding failed: {}", err);
+        Flash::error(
+            Redirect::to(uri!(home(_, _))),
+            "Unable to look up that location, please try again later.",
+        )
+    })?;
+
+    match locations.first() {
+        Some(location) => {
+            let location = location.localised(language_preference.languages());
+            let url = location_url(
+                &location,
+                gazetteer,
+                geocoding_cache,
+                slug_cache,
+                &config.openweather_api_key,
+            )
+            .await;
+            Ok(Redirect::to(url))
+        }
+        None => Err(Flash::warning(
+            Redirect::to(uri!(home(_, _))),
+            "No named location was found near those coordinates.",
+        )),
     }
 }
 
@@ -144,50 +525,504 @@ async fn location<'f>(
 struct ForecastContext<'f> {
     title: String,
     forecast: OneCall,
-    unit: TemperatureUnit,
+    timezone: Timezone,
+    units: Units,
+    time_format: DateTimeFormat,
+    place: Place,
+    favourite: bool,
     flash: Option<FlashMessage<'f>>,
 }
 
+/// A forecast reached by coordinates, which refers search engines to the slug URL if there is one
+/// that leads back to the same place
+#[derive(Responder)]
+enum CoordinateForecast<'f> {
+    Canonical(ForecastContext<'f>, Header<'static>),
+    Plain(ForecastContext<'f>),
+    Rounded(Redirect),
+}
+
+/// Forecast for a point, e.g. `/forecast/au/-27.4679/153.0281/Brisbane`
+///
+/// Coordinates with more precision than [Coordinates::rounded] are redirected so that URLs (and
+/// cached forecasts) for the same place converge.
+#[allow(clippy::too_many_arguments)]
 #[get("/forecast/<country>/<lat>/<lon>/<name>")]
 async fn forecast<'f>(
-    mut country: String,
+    country: Result<Country, &str>,
     lat: f32,
     lon: f32,
     name: Option<String>,
     flash: Option<FlashMessage<'f>>,
+    unit_preference: UnitPreference,
+    date_time_preference: DateTimePreference,
+    history: History,
+    cookies: &CookieJar<'_>,
     config: &State<DewpointConfig>,
     weather_cache: &State<WeatherCache>,
-) -> ForecastContext<'f> {
-    country.make_ascii_uppercase();
-    let unit = match country.as_str() {
-        // list from https://worldpopulationreview.com/country-rankings/countries-that-use-fahrenheit
-        | "BS" // Bahamas
-        | "FM" // Micronesia (Federated States of)
-        | "KY" // Cayman Islands
-        | "LR" // Liberia
-        | "MH" // Marshall Islands
-        | "PW" // Palau
-        | "US" // United States
-        => TemperatureUnit::Fahrenheit,
-        _ => TemperatureUnit::Celsius,
+    geocoding_cache: &State<GeocodingCache>,
+    gazetteer: &State<Gazetteer>,
+    slug_cache: &State<SlugCache>,
+) -> Result<CoordinateForecast<'f>, Status> {
+    let country = country.map_err(|_| Status::NotFound)?;
+    let coordinates = Coordinates::new(lat, lon).ok_or(Status::BadRequest)?;
+    let name = name.unwrap_or_else(|| String::from("Unknown"));
+    let rounded = coordinates.rounded();
+    if rounded != coordinates {
+        let url = uri!(forecast(&country, rounded.lat, rounded.lon, name));
+        return Ok(CoordinateForecast::Rounded(Redirect::permanent(url)));
+    }
+
+    let canonical = canonical_url(
+        &name,
+        coordinates,
+        &country,
+        gazetteer,
+        geocoding_cache,
+        slug_cache,
+        &config.openweather_api_key,
+    )
+    .await;
+    let place = Place {
+        country: country.code().to_ascii_lowercase(),
+        lat,
+        lon,
+        name,
     };
+    let forecast = render_forecast(
+        place,
+        flash,
+        unit_preference,
+        date_time_preference,
+        history,
+        cookies,
+        config,
+        weather_cache,
+    )
+    .await?;
 
-    let url = format!("https://api.openweathermap.org/data/2.5/onecall?lat={lat}&lon={lon}&exclude={exclude}&appid={apikey}",
-    lat=lat, lon=lon, exclude="minutely,hourly,alerts", apikey=config.openweather_api_key);
-    let forecast = weather_cache
-        .get_or_fetch(url)
+    match canonical {
+        Some(canonical) => {
+            let link = format!("<{}>; rel=\"canonical\"", canonical);
+            Ok(CoordinateForecast::Canonical(
+                forecast,
+                Header::new("Link", link),
+            ))
+        }
+        None => Ok(CoordinateForecast::Plain(forecast)),
+    }
+}
+
+/// The slug URL of the place called `name` at `coordinates`, if following it leads back there
+///
+/// Names of coordinate searches, and places whose name resolves to somewhere else (e.g. a smaller
+/// town sharing a larger one's name), have none.
+async fn canonical_url(
+    name: &str,
+    coordinates: Coordinates,
+    country: &Country,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    slug_cache: &SlugCache,
+    api_key: &str,
+) -> Option<String> {
+    let slug = slugify(name);
+    if slug.is_empty() || coordinates::parse(name).is_some() {
+        return None;
+    }
+
+    let location = slug_candidates(&slug, country, gazetteer, geocoding_cache, api_key)
         .await
-        .expect("FIXME weather error");
+        .map_err(|err| warn!("Unable to find the slug URL for {}: {}", name, err))
+        .ok()?
+        .into_iter()
+        .find(|location| location.coordinates().rounded() == coordinates)?;
+    resolvable_slug_url(&location, gazetteer, geocoding_cache, slug_cache, api_key).await
+}
+
+/// The URL to link to for the forecast for `location`: its slug URL if that leads back to it,
+/// otherwise its coordinate URL
+///
+/// Without the check, places sharing a name (and lacking a state to tell them apart, like postcode
+/// results) would all link to whichever one the slug resolves to.
+async fn location_url(
+    location: &Location,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    slug_cache: &SlugCache,
+    api_key: &str,
+) -> String {
+    match resolvable_slug_url(location, gazetteer, geocoding_cache, slug_cache, api_key).await {
+        Some(url) => url,
+        None => location.coordinate_url().to_string(),
+    }
+}
 
-    ForecastContext {
-        title: format!(
-            "Forecast for {}",
-            name.unwrap_or_else(|| String::from("Unknown"))
-        ),
+/// The slug URL of `location`, if following it leads back to the same (rounded) coordinates
+async fn resolvable_slug_url(
+    location: &Location,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    slug_cache: &SlugCache,
+    api_key: &str,
+) -> Option<String> {
+    let (state, name) = location.slugs()?;
+    let url = location.slug_url()?.to_string();
+    let resolved = resolve_slug(
+        &url,
+        state.as_deref(),
+        &name,
+        &location.country,
+        gazetteer,
+        geocoding_cache,
+        slug_cache,
+        api_key,
+    )
+    .await
+    .map_err(|err| warn!("Unable to resolve {}: {}", url, err))
+    .ok()??;
+    (resolved.coordinates().rounded() == location.coordinates().rounded()).then_some(url)
+}
+
+/// Places in `country` whose name has the slug `name`, best match first
+///
+/// The gazetteer is searched by slug, so names with accents or punctuation are found. OpenWeather is
+/// searched for the words of the slug when the gazetteer has no match.
+async fn slug_candidates(
+    name: &str,
+    country: &Country,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    api_key: &str,
+) -> Result<Vec<Location>, reqwest::Error> {
+    let locations = gazetteer.find_slug(name, country);
+    if !locations.is_empty() {
+        return Ok(locations);
+    }
+
+    let locations = geocoding_cache
+        .get_or_fetch(&name.replace('-', " "), country, api_key)
+        .await?;
+    Ok(locations
+        .into_iter()
+        .filter(|location| slugify(location.name()) == name)
+        .collect())
+}
+
+/// The place the slug URL `path` refers to: the first candidate named `name` in `state`
+///
+/// Places without a state match any. Results (including failures to resolve) are cached, but only
+/// ever worked out from the slugs, so a URL leads to the same place whoever follows it.
+#[allow(clippy::too_many_arguments)]
+async fn resolve_slug(
+    path: &str,
+    state: Option<&str>,
+    name: &str,
+    country: &Country,
+    gazetteer: &Gazetteer,
+    geocoding_cache: &GeocodingCache,
+    slug_cache: &SlugCache,
+    api_key: &str,
+) -> Result<Option<Location>, reqwest::Error> {
+    if let Some(location) = slug_cache.get(path).await {
+        return Ok(location);
+    }
+
+    let location = slug_candidates(name, country, gazetteer, geocoding_cache, api_key)
+        .await?
+        .into_iter()
+        .find(|location| match (state, location.state()) {
+            (Some(state), Some(location_state)) => slugify(location_state) == state,
+            _ => true,
+        });
+    slug_cache.insert(path.to_string(), location.clone()).await;
+    Ok(location)
+}
+
+/// Forecast for a place identified by slugs, e.g. `/au/brisbane` or `/au/queensland/brisbane`
+///
+/// Ranked after the static files so they aren't mistaken for places.
+#[allow(clippy::too_many_arguments)]
+#[get("/<country>/<slugs..>", rank = 20)]
+async fn place_forecast<'f>(
+    country: &str,
+    slugs: PathBuf,
+    flash: Option<FlashMessage<'f>>,
+    unit_preference: UnitPreference,
+    date_time_preference: DateTimePreference,
+    history: History,
+    cookies: &CookieJar<'_>,
+    config: &State<DewpointConfig>,
+    weather_cache: &State<WeatherCache>,
+    geocoding_cache: &State<GeocodingCache>,
+    gazetteer: &State<Gazetteer>,
+    slug_cache: &State<SlugCache>,
+    language_preference: LanguagePreference,
+) -> Result<ForecastContext<'f>, Status> {
+    let country = Country::from_code(country).ok_or(Status::NotFound)?;
+    let slugs = slugs
+        .iter()
+        .map(|slug| slug.to_str())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Status::NotFound)?;
+    let (state, name) = match slugs.as_slice() {
+        [name] => (None, *name),
+        [state, name] => (Some(*state), *name),
+        _ => return Err(Status::NotFound),
+    };
+    // Anything that couldn't have come from `Location::slug_url` is turned away without a search
+    if slugs.iter().any(|slug| slugify(slug) != *slug) {
+        return Err(Status::NotFound);
+    }
+
+    let path = uri!(place_forecast(
+        country.code().to_ascii_lowercase(),
+        slugs.iter().collect::<PathBuf>()
+    ))
+    .to_string();
+    let location = resolve_slug(
+        &path,
+        state,
+        name,
+        &country,
+        gazetteer,
+        geocoding_cache,
+        slug_cache,
+        &config.openweather_api_key,
+    )
+    .await
+    .map_err(|err| {
+        error!("Unable to resolve {}: {}", path, err);
+        Status::BadGateway
+    })?
+    .ok_or(Status::NotFound)?
+    .localised(language_preference.languages());
+
+    let coordinates = location.coordinates().rounded();
+    let place = Place {
+        country: country.code().to_ascii_lowercase(),
+        lat: coordinates.lat,
+        lon: coordinates.lon,
+        name: location.name().to_string(),
+    };
+    render_forecast(
+        place,
+        flash,
+        unit_preference,
+        date_time_preference,
+        history,
+        cookies,
+        config,
+        weather_cache,
+    )
+    .await
+}
+
+/// Fetch the forecast for `place` and record the visit in the history
+///
+/// Fails with [Status::BadGateway] if OpenWeather can't provide the forecast.
+#[allow(clippy::too_many_arguments)]
+async fn render_forecast<'f>(
+    place: Place,
+    flash: Option<FlashMessage<'f>>,
+    unit_preference: UnitPreference,
+    date_time_preference: DateTimePreference,
+    mut history: History,
+    cookies: &CookieJar<'_>,
+    config: &DewpointConfig,
+    weather_cache: &WeatherCache,
+) -> Result<ForecastContext<'f>, Status> {
+    let units = unit_preference.resolve(&place.country);
+    let time_format = date_time_preference.resolve(&place.country);
+
+    let url = onecall_url(place.coordinates(), &config.openweather_api_key);
+    let forecast = weather_cache.get_or_fetch(url).await.map_err(|err| {
+        error!("Unable to fetch the forecast for {}: {}", place.name, err);
+        Status::BadGateway
+    })?;
+    let timezone = forecast.tz();
+
+    history.visit(place.clone());
+    history.save(cookies);
+
+    Ok(ForecastContext {
+        title: format!("Forecast for {}", place.name),
         forecast,
-        unit,
+        timezone,
+        units,
+        time_format,
+        favourite: history.is_favourite(&place),
+        place,
         flash,
+    })
+}
+
+/// The OneCall URL for `coordinates`, rounded so that nearby points share a cached forecast
+fn onecall_url(coordinates: Coordinates, api_key: &str) -> String {
+    let Coordinates { lat, lon } = coordinates.rounded();
+    format!("https://api.openweathermap.org/data/2.5/onecall?lat={lat}&lon={lon}&exclude={exclude}&appid={apikey}",
+    lat=lat, lon=lon, exclude="minutely,hourly,alerts", apikey=api_key)
+}
+
+/// Star or unstar a forecast location
+#[post("/favourite", data = "<place>")]
+fn favourite(
+    _same_origin: SameOrigin,
+    place: Form<Place>,
+    mut history: History,
+    cookies: &CookieJar<'_>,
+) -> Redirect {
+    let place = place.into_inner();
+    let url = place.url().to_string();
+    history.toggle_favourite(place);
+    history.save(cookies);
+    Redirect::to(url)
+}
+
+/// Forget the recently viewed forecasts
+#[post("/history/clear")]
+fn clear_history(
+    _same_origin: SameOrigin,
+    mut history: History,
+    cookies: &CookieJar<'_>,
+) -> Flash<Redirect> {
+    history.clear_recent();
+    history.save(cookies);
+    Flash::success(Redirect::to(uri!(home(_, _))), "History cleared.")
+}
+
+#[derive(Template)]
+#[template(path = "compare.html")]
+struct CompareContext<'f> {
+    title: String,
+    places: Vec<Place>,
+    rows: Vec<CompareRow>,
+    flash: Option<FlashMessage<'f>>,
+}
+
+/// One day of the comparison, with a cell per location
+struct CompareRow {
+    date: String,
+    cells: Vec<Option<CompareCell>>,
+}
+
+struct CompareCell {
+    dew_point: String,
+    max: String,
+    comfort: Comfort,
+}
+
+/// Compare the forecasts for several locations side by side
+///
+/// Each `location` is `country/lat/lon/name`, as in the forecast URL. Units and date formats are
+/// those of the first location. There's a row for each local date in any of the forecasts.
+#[get("/compare?<location>")]
+async fn compare<'f>(
+    location: Vec<String>,
+    flash: Option<FlashMessage<'f>>,
+    unit_preference: UnitPreference,
+    date_time_preference: DateTimePreference,
+    config: &State<DewpointConfig>,
+    weather_cache: &State<WeatherCache>,
+) -> Result<CompareContext<'f>, Flash<Redirect>> {
+    let error = |message| Flash::error(Redirect::to(uri!(home(_, _))), message);
+    if location.is_empty() {
+        return Err(error("Choose some locations to compare."));
     }
+    if location.len() > config.compare_limit {
+        return Err(error("Too many locations to compare."));
+    }
+    let places = location
+        .iter()
+        .map(|location| parse_place(location))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| error("Unable to compare those locations."))?;
+
+    let api_key = &config.openweather_api_key;
+    let forecasts = future::try_join_all(
+        places
+            .iter()
+            .map(|place| weather_cache.get_or_fetch(onecall_url(place.coordinates(), api_key))),
+    )
+    .await
+    .map_err(|err| {
+        error!("Unable to fetch forecasts to compare: {}", err);
+        error("Unable to fetch the forecasts, please try again later.")
+    })?;
+
+    let country = &places[0].country;
+    let units = unit_preference.resolve(country);
+    let time_format = date_time_preference.resolve(country);
+    // Each location's days are in its own timezone, so rows are matched up by local date rather
+    // than position
+    let mut rows: BTreeMap<NaiveDate, CompareRow> = BTreeMap::new();
+    for (column, forecast) in forecasts.iter().enumerate() {
+        let timezone = forecast.tz();
+        for day in &forecast.daily {
+            let row = rows
+                .entry(day.dt.local_date(&timezone))
+                .or_insert_with(|| CompareRow {
+                    date: day.dt.day_date(&timezone, &time_format),
+                    cells: (0..forecasts.len()).map(|_| None).collect(),
+                });
+            row.cells[column] = Some(CompareCell {
+                dew_point: day.dew_point(&units),
+                max: day.max(&units),
+                comfort: day.comfort(),
+            });
+        }
+    }
+    let rows = rows.into_values().collect();
+
+    Ok(CompareContext {
+        title: String::from("Compare"),
+        places,
+        rows,
+        flash,
+    })
+}
+
+/// Parse a `country/lat/lon/name` location
+fn parse_place(location: &str) -> Option<Place> {
+    let mut parts = location.splitn(4, '/');
+    let country = parts.next()?;
+    let lat = parts.next()?.parse::<f32>().ok()?;
+    let lon = parts.next()?.parse::<f32>().ok()?;
+    let name = parts.next()?;
+    let country = Country::from_code(country)?;
+    let coordinates = Coordinates::new(lat, lon)?.rounded();
+
+    Some(Place {
+        country: country.code().to_ascii_lowercase(),
+        lat: coordinates.lat,
+        lon: coordinates.lon,
+        name: name.to_string(),
+    })
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorContext {
+    title: String,
+    message: &'static str,
+    flash: Option<FlashMessage<'static>>,
+}
+
+/// Render errors within the site layout
+#[catch(default)]
+fn error(status: Status, _req: &Request<'_>) -> (Status, ErrorContext) {
+    let message = match status.code {
+        400 => "The request was invalid. Check the address and try again.",
+        404 => "There's nothing here. Check the address or search for a location.",
+        502 => "The weather service couldn't be reached, please try again later.",
+        _ => "Something went wrong, please try again later.",
+    };
+    let context = ErrorContext {
+        title: status.reason_lossy().to_string(),
+        message,
+        flash: None,
+    };
+    (status, context)
 }
 
 #[get("/robots.txt")]
@@ -208,7 +1043,7 @@ mod filters {
 
     pub fn url(name: &str) -> ::askama::Result<String> {
         match name {
-            "home" => Ok(uri!(home()).to_string()),
+            "home" => Ok(uri!(home(_, _)).to_string()),
             "about" => Ok(uri!(about()).to_string()),
             "acknowledgements" => Ok(uri!(acknowledgements()).to_string()),
             _ => Err(askama::Error::Fmt(fmt::Error)),
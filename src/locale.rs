@@ -0,0 +1,223 @@
+//! Locale-aware date and time formatting
+
+use std::str::FromStr;
+
+use chrono::Locale;
+
+use crate::country;
+
+/// How times of day are presented
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Clock {
+    TwelveHour,
+    TwentyFourHour,
+}
+
+/// The locale and clock used to present dates and times
+#[derive(Copy, Clone, Debug)]
+pub struct DateTimeFormat {
+    pub locale: Locale,
+    pub clock: Clock,
+}
+
+impl Clock {
+    /// The clock customarily used in a country, identified by its ISO 3166-1 alpha-2 code
+    pub fn for_country(code: &str) -> Self {
+        country::info(code)
+            .map(|info| info.clock)
+            .unwrap_or(Clock::TwentyFourHour)
+    }
+
+    /// All clocks, in the order they are presented to the user
+    pub fn all() -> [Clock; 2] {
+        [Clock::TwelveHour, Clock::TwentyFourHour]
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Clock::TwelveHour => "12h",
+            Clock::TwentyFourHour => "24h",
+        }
+    }
+}
+
+impl FromStr for Clock {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "12h" => Ok(Clock::TwelveHour),
+            "24h" => Ok(Clock::TwentyFourHour),
+            _ => Err(()),
+        }
+    }
+}
+
+impl DateTimeFormat {
+    /// The customary date and time format for a country
+    pub fn for_country(code: &str) -> Self {
+        DateTimeFormat {
+            locale: locale_for_country(code),
+            clock: Clock::for_country(code),
+        }
+    }
+
+    /// Use `locale` with the clock customary in its region
+    pub fn for_locale(locale: Locale) -> Self {
+        let clock = region(locale)
+            .map(|region| Clock::for_country(&region))
+            .unwrap_or(Clock::TwentyFourHour);
+        DateTimeFormat { locale, clock }
+    }
+
+    /// `strftime` pattern for a time of day
+    pub fn time_pattern(&self) -> &'static str {
+        match self.clock {
+            Clock::TwelveHour => "%I:%M %p",
+            Clock::TwentyFourHour => "%H:%M",
+        }
+    }
+
+    /// `strftime` pattern for the day of the week and date, without the year
+    pub fn date_pattern(&self) -> &'static str {
+        let name = self.locale.to_string();
+        match name.split('_').next().unwrap_or_default() {
+            "cs" | "da" | "de" | "fi" | "hr" | "nb" | "sk" | "sl" => "%A, %-d. %B",
+            "ja" | "zh" => "%-m月%-d日 %A",
+            "ko" => "%-m월 %-d일 %A",
+            _ if matches!(region(self.locale).as_deref(), Some("US") | Some("PH")) => "%A, %B %-d",
+            _ => "%A, %-d %B",
+        }
+    }
+}
+
+/// The default locale for a country, identified by its ISO 3166-1 alpha-2 code
+///
+/// Falls back to the POSIX locale (English names) if there is no locale for the country.
+pub fn locale_for_country(code: &str) -> Locale {
+    country::info(code)
+        .map(|info| info.locale)
+        .unwrap_or(Locale::POSIX)
+}
+
+/// Find the locale for a BCP 47 language tag such as `en-AU` or `de`, for a forecast in
+/// `country_code`
+///
+/// The language is kept but the forecast's country takes precedence over the tag's region, so a
+/// visitor with `en-US` sees Australian forecasts with `en_AU` dates rather than US ones. If the
+/// language has no locale in that country the tag's own region is used, then the language's
+/// (`de` → `de_DE`).
+pub fn locale_for_language_tag(tag: &str, country_code: &str) -> Option<Locale> {
+    let mut parts = tag.split('-');
+    let language = parts.next()?.to_ascii_lowercase();
+    let country_code = country_code.to_ascii_uppercase();
+    Locale::from_str(&format!("{}_{}", language, country_code))
+        .ok()
+        .or_else(|| match parts.next() {
+            Some(region) if region.len() == 2 => {
+                Locale::from_str(&format!("{}_{}", language, region.to_ascii_uppercase())).ok()
+            }
+            _ => None,
+        })
+        .or_else(|| locale_for_language(&language, &country_code))
+}
+
+/// Parse an `Accept-Language` header value into language tags, most preferred first
+///
+/// The wildcard and languages with a weight of zero (not acceptable) are dropped. Malformed
+/// weights are treated as the default of 1.
+pub fn parse_accept_language(header: &str) -> Vec<&str> {
+    let mut languages = header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .filter(|q| (0.0..=1.).contains(q))
+                .unwrap_or(1.);
+            (quality > 0.).then_some((tag, quality))
+        })
+        .collect::<Vec<_>>();
+    // Stable sort keeps the header order for equal weights
+    languages.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
+
+fn locale_for_language(language: &str, country_code: &str) -> Option<Locale> {
+    Locale::from_str(&format!("{}_{}", language, country_code))
+        .or_else(|_| Locale::from_str(&format!("{}_{}", language, language.to_ascii_uppercase())))
+        .ok()
+}
+
+/// The region (country code) part of a locale, e.g. `AU` for `en_AU`
+fn region(locale: Locale) -> Option<String> {
+    let name = locale.to_string();
+    let region = name.split('_').nth(1)?;
+    Some(region.split('@').next().unwrap_or(region).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language_is_ordered_by_weight() {
+        assert_eq!(
+            parse_accept_language("fr;q=0.5, en-AU, de;q=0.8, en;q=0.8"),
+            ["en-AU", "de", "en", "fr"]
+        );
+    }
+
+    #[test]
+    fn accept_language_drops_wildcard_and_unacceptable() {
+        assert_eq!(parse_accept_language("*, en-GB;q=0.9, fr;q=0"), ["en-GB"]);
+        assert!(parse_accept_language("*").is_empty());
+    }
+
+    #[test]
+    fn malformed_accept_language() {
+        assert!(parse_accept_language("").is_empty());
+        assert!(parse_accept_language(" , ;q=0.5,").is_empty());
+        assert_eq!(
+            parse_accept_language("de;q=abc, en;q=0.9, fr;q=7"),
+            ["de", "fr", "en"]
+        );
+    }
+
+    #[test]
+    fn language_tag_uses_forecast_country() {
+        assert_eq!(locale_for_language_tag("en-US", "AU"), Some(Locale::en_AU));
+        assert_eq!(locale_for_language_tag("en-US", "us"), Some(Locale::en_US));
+        assert_eq!(locale_for_language_tag("de", "AT"), Some(Locale::de_AT));
+    }
+
+    #[test]
+    fn language_tag_without_locale_in_country() {
+        assert_eq!(locale_for_language_tag("en-US", "DE"), Some(Locale::en_US));
+        assert_eq!(locale_for_language_tag("de", "AU"), Some(Locale::de_DE));
+        assert_eq!(locale_for_language_tag("xx", "AU"), None);
+    }
+
+    #[test]
+    fn date_patterns() {
+        let pattern = |locale| DateTimeFormat::for_locale(locale).date_pattern();
+        assert_eq!(pattern(Locale::en_AU), "%A, %-d %B");
+        assert_eq!(pattern(Locale::en_US), "%A, %B %-d");
+        assert_eq!(pattern(Locale::de_DE), "%A, %-d. %B");
+        assert_eq!(pattern(Locale::ja_JP), "%-m月%-d日 %A");
+    }
+
+    #[test]
+    fn clock_follows_locale_region() {
+        assert_eq!(
+            DateTimeFormat::for_locale(Locale::en_US).clock,
+            Clock::TwelveHour
+        );
+        assert_eq!(
+            DateTimeFormat::for_locale(Locale::en_GB).clock,
+            Clock::TwentyFourHour
+        );
+    }
+}
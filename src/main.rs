@@ -1,36 +1,78 @@
 #[macro_use]
 extern crate rocket;
 
-use ip2location::LocationDB as GeoDB;
+use std::path::PathBuf;
+
 use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket::tokio::select;
 use rocket::tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
-use dewpoint::{home, WeatherCache};
+use dewpoint::gazetteer::Gazetteer;
+use dewpoint::geoip::GeoIpBackendKind;
+use dewpoint::{home, GeocodingCache, SlugCache, WeatherCache};
 use dewpoint::{Countries, DewpointConfig, Ip2Location};
 
 const CLEAN_PERIOD: u64 = 4 * 60 * 60; // 4 hours (in seconds)
+const RELOAD_PERIOD: u64 = 10 * 60; // 10 minutes (in seconds)
+const GEODB_FILES: [&str; 3] = [
+    "IP2LOCATION-LITE-DB5.BIN", // country, region, city, coordinates
+    "IP2LOCATION-LITE-DB3.BIN", // country, region, city
+    "IP2LOCATION-LITE-DB1.BIN", // country
+];
 
 #[launch]
 fn rocket() -> _ {
-    let geodb = Ip2Location::new(
-        GeoDB::from_file("IP2LOCATION-LITE-DB1.BIN").expect("FIXME: unable to open geo ip db"),
-    );
     let countries = Countries::new();
-    let weather_cache = WeatherCache::new();
+    let rocket = rocket::build();
+    let config: DewpointConfig = rocket
+        .figment()
+        .extract()
+        .expect("unable to read dewpoint config");
+    let weather_cache = WeatherCache::new(config.dew_point_tolerance);
+    let geocoding_cache = GeocodingCache::new();
+    let slug_cache = SlugCache::new();
+    // Use the most detailed IP2Location database available if one isn't configured
+    let geodb_path = config
+        .geoip_path
+        .clone()
+        .or_else(|| match config.geoip_backend {
+            GeoIpBackendKind::Ip2Location => GEODB_FILES
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.exists()),
+            GeoIpBackendKind::MaxMind => None,
+        });
+    let geodb = Ip2Location::new(config.geoip_backend, geodb_path);
+    let gazetteer = match &config.gazetteer_path {
+        Some(path) => Gazetteer::from_file(path, config.gazetteer_admin1_path.as_ref())
+            .unwrap_or_else(|err| {
+                error!("Unable to load gazetteer {}: {}", path, err);
+                Gazetteer::empty()
+            }),
+        None => Gazetteer::empty(),
+    };
 
-    rocket::build()
-        .manage(geodb)
+    rocket
+        .manage(geodb.clone())
         .manage(countries)
         .manage(weather_cache.clone())
+        .manage(geocoding_cache.clone())
+        .manage(gazetteer)
+        .manage(slug_cache.clone())
         .attach(AdHoc::config::<DewpointConfig>())
-        .attach(cache_cleaner(weather_cache))
+        .attach(cache_cleaner(weather_cache, geocoding_cache, slug_cache))
+        .attach(geodb_reloader(geodb))
         .mount("/", home::routes())
+        .register("/", home::catchers())
         .mount("/public", FileServer::from("public"))
 }
 
-fn cache_cleaner(cache: WeatherCache) -> AdHoc {
+fn cache_cleaner(
+    weather_cache: WeatherCache,
+    geocoding_cache: GeocodingCache,
+    slug_cache: SlugCache,
+) -> AdHoc {
     AdHoc::on_liftoff("Cache cleaner", |rocket| {
         Box::pin(async move {
             let mut shutdown = rocket.shutdown();
@@ -44,8 +86,14 @@ fn cache_cleaner(cache: WeatherCache) -> AdHoc {
                     select! {
                         _ = interval.tick() => {
                             info!("Cleaning weather cache");
-                            cache.clean().await;
+                            weather_cache.clean().await;
                             info!("Weather cache cleaned");
+                            info!("Cleaning geocoding cache");
+                            geocoding_cache.clean().await;
+                            info!("Geocoding cache cleaned");
+                            info!("Cleaning slug cache");
+                            slug_cache.clean().await;
+                            info!("Slug cache cleaned");
                         },
                         _ = &mut shutdown => break,
                     };
@@ -54,3 +102,23 @@ fn cache_cleaner(cache: WeatherCache) -> AdHoc {
         })
     })
 }
+
+fn geodb_reloader(geodb: Ip2Location) -> AdHoc {
+    AdHoc::on_liftoff("IP database reloader", |rocket| {
+        Box::pin(async move {
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let period = Duration::from_secs(RELOAD_PERIOD);
+                let start = Instant::now() + period;
+                let mut interval = time::interval_at(start, period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    select! {
+                        _ = interval.tick() => geodb.reload_if_changed().await,
+                        _ = &mut shutdown => break,
+                    };
+                }
+            });
+        })
+    })
+}
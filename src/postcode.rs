@@ -0,0 +1,151 @@
+//! Postal code detection
+//!
+//! Used to decide whether search input should be looked up as a postal code rather than a place
+//! name. Formats are from <https://en.wikipedia.org/wiki/List_of_postal_codes>.
+
+use crate::country::Country;
+
+/// Postal code formats by ISO 3166-1 alpha-2 country code
+///
+/// In the patterns `N` matches a digit, `A` a letter, and `?` either. Other characters match
+/// themselves, except spaces, which are optional in the input.
+const FORMATS: &[(&str, &[&str])] = &[
+    ("AR", &["NNNN", "ANNNNAAA"]),
+    ("AT", &["NNNN"]),
+    ("AU", &["NNNN"]),
+    ("BE", &["NNNN"]),
+    ("BG", &["NNNN"]),
+    ("BR", &["NNNNN-NNN", "NNNNNNNN"]),
+    ("CA", &["ANA NAN"]),
+    ("CH", &["NNNN"]),
+    ("CN", &["NNNNNN"]),
+    ("CZ", &["NNN NN"]),
+    ("DE", &["NNNNN"]),
+    ("DK", &["NNNN"]),
+    ("EE", &["NNNNN"]),
+    ("ES", &["NNNNN"]),
+    ("FI", &["NNNNN"]),
+    ("FR", &["NNNNN"]),
+    (
+        "GB",
+        &[
+            "AN NAA", "ANN NAA", "AAN NAA", "AANN NAA", "ANA NAA", "AANA NAA",
+        ],
+    ),
+    ("GR", &["NNN NN"]),
+    ("HR", &["NNNNN"]),
+    ("HU", &["NNNN"]),
+    ("IE", &["ANN ????", "ANA ????"]),
+    ("IN", &["NNNNNN", "NNN NNN"]),
+    ("IS", &["NNN"]),
+    ("IT", &["NNNNN"]),
+    ("JP", &["NNN-NNNN", "NNNNNNN"]),
+    ("KR", &["NNNNN"]),
+    ("LT", &["LT-NNNNN", "NNNNN"]),
+    ("LU", &["NNNN", "L-NNNN"]),
+    ("LV", &["LV-NNNN"]),
+    ("MX", &["NNNNN"]),
+    ("MY", &["NNNNN"]),
+    ("NL", &["NNNN AA", "NNNN"]),
+    ("NO", &["NNNN"]),
+    ("NZ", &["NNNN"]),
+    ("PH", &["NNNN"]),
+    ("PL", &["NN-NNN"]),
+    ("PT", &["NNNN-NNN"]),
+    ("RO", &["NNNNNN"]),
+    ("RU", &["NNNNNN"]),
+    ("SE", &["NNN NN"]),
+    ("SG", &["NNNNNN"]),
+    ("SI", &["NNNN"]),
+    ("SK", &["NNN NN"]),
+    ("TH", &["NNNNN"]),
+    ("TR", &["NNNNN"]),
+    ("TW", &["NNN", "NNNNN"]),
+    ("UA", &["NNNNN"]),
+    ("US", &["NNNNN", "NNNNN-NNNN"]),
+    ("ZA", &["NNNN"]),
+];
+
+/// Returns `true` if `input` matches one of the postal code formats used in `country`
+pub fn looks_like_postcode(country: &Country, input: &str) -> bool {
+    let input = input.trim();
+    FORMATS.iter().any(|(code, formats)| {
+        *code == country.code() && formats.iter().any(|format| matches_format(format, input))
+    })
+}
+
+fn matches_format(format: &str, input: &str) -> bool {
+    let mut format = format.chars().filter(|c| *c != ' ');
+    let mut input = input.chars().filter(|c| *c != ' ');
+    loop {
+        match (format.next(), input.next()) {
+            (Some('N'), Some(c)) if c.is_ascii_digit() => {}
+            (Some('A'), Some(c)) if c.is_ascii_alphabetic() => {}
+            (Some('?'), Some(c)) if c.is_ascii_alphanumeric() => {}
+            (Some(f), Some(c)) if !"NA?".contains(f) && f.eq_ignore_ascii_case(&c) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn postcode(country: &str, input: &str) -> bool {
+        looks_like_postcode(&Country::from_code(country).unwrap(), input)
+    }
+
+    #[test]
+    fn accepts_postcodes() {
+        let cases = [
+            ("AU", "4000"),
+            ("US", "90210"),
+            ("US", "90210-1234"),
+            ("GB", "SW1A 1AA"),
+            ("GB", "sw1a1aa"),
+            ("GB", "M1 1AE"),
+            ("CA", "K1A 0B1"),
+            ("NL", "1012 AB"),
+            ("NL", "1012"),
+            ("JP", "100-0001"),
+            ("BR", "01310-100"),
+            ("PL", "00-950"),
+            ("LT", "LT-01100"),
+            ("LV", "lv-1050"),
+            ("IE", "D02 X285"),
+            ("SE", "114 55"),
+            ("AU", " 2000 "),
+        ];
+        for (country, input) in cases {
+            assert!(postcode(country, input), "{} {}", country, input);
+        }
+    }
+
+    #[test]
+    fn rejects_non_postcodes() {
+        let cases = [
+            ("AU", "Brisbane"),
+            ("AU", "400"),
+            ("AU", "40000"),
+            ("US", "9021"),
+            ("US", "90210-12"),
+            ("GB", "SW1A"),
+            ("CA", "123 456"),
+            ("NL", "AB 1012"),
+            ("JP", "100-000"),
+            ("PL", "00950-"),
+            ("LV", "LT-1050"),
+            ("AU", ""),
+        ];
+        for (country, input) in cases {
+            assert!(!postcode(country, input), "{} {}", country, input);
+        }
+    }
+
+    #[test]
+    fn countries_without_formats_have_no_postcodes() {
+        assert!(!postcode("AQ", "1234"));
+    }
+}
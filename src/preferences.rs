@@ -0,0 +1,228 @@
+//! Request guards for user preferences
+
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use rocket::http::{Cookie, CookieJar};
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::country::{self, Country};
+use crate::locale::{self, Clock, DateTimeFormat};
+use crate::weather::{PressureUnit, UnitSystem, Units, WindSpeedUnit};
+
+const UNITS: &str = "units";
+const WIND: &str = "wind";
+const PRESSURE: &str = "pressure";
+const CLOCK: &str = "clock";
+const COUNTRY: &str = "country";
+
+/// The units explicitly chosen by the user, if any
+///
+/// The unit system is taken from the `units` query parameter, falling back on the units cookie.
+/// When present in the query parameter the choice is remembered in the (private) cookie for
+/// subsequent requests. Wind speed and pressure units that override the system's are chosen the
+/// same way with the `wind` and `pressure` parameters.
+pub struct UnitPreference {
+    system: Option<UnitSystem>,
+    wind_speed: Option<WindSpeedUnit>,
+    pressure: Option<PressureUnit>,
+}
+
+/// The user's preferred languages, from the `Accept-Language` header, most preferred first
+pub struct LanguagePreference(Vec<String>);
+
+/// The user's date and time preferences
+///
+/// The clock is chosen via the `clock` query parameter and remembered in a cookie like
+/// [UnitPreference]. The locale comes from the `Accept-Language` header.
+pub struct DateTimePreference {
+    clock: Option<Clock>,
+    languages: Vec<String>,
+}
+
+impl UnitPreference {
+    /// Resolve the effective units, using the country's unit system if the user has no preference
+    pub fn resolve(&self, country_code: &str) -> Units {
+        let system = self
+            .system
+            .unwrap_or_else(|| UnitSystem::for_country(country_code));
+        Units::new(system, self.wind_speed, self.pressure)
+    }
+}
+
+/// Clues to the user's country besides their IP address
+pub struct CountryHints {
+    accept_language: Option<Country>,
+    last_search: Option<Country>,
+}
+
+impl LanguagePreference {
+    pub fn languages(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl CountryHints {
+    /// Infer the user's country from, in order of preference, the IP database, the region of
+    /// their preferred language, or the country of their last search
+    pub fn infer(&self, ip_country: Option<Country>) -> Option<Country> {
+        ip_country
+            .or_else(|| self.accept_language.clone())
+            .or_else(|| self.last_search.clone())
+    }
+}
+
+/// Remember the country searched in, for [CountryHints]
+pub fn remember_country(cookies: &CookieJar<'_>, country: &Country) {
+    cookies.add_private(Cookie::build((COUNTRY, country.code().to_string())).permanent());
+}
+
+/// The country of the user's last search
+pub fn country_from_cookie(cookies: &CookieJar<'_>) -> Option<Country> {
+    cookies
+        .get_private(COUNTRY)
+        .and_then(|cookie| Country::from_code(cookie.value()))
+}
+
+impl DateTimePreference {
+    /// Resolve the effective date and time format for a location in `country_code`
+    ///
+    /// The first `Accept-Language` with a known locale is used, otherwise the country default.
+    /// An explicit clock preference overrides the clock customary for the locale.
+    pub fn resolve(&self, country_code: &str) -> DateTimeFormat {
+        let mut format = self
+            .languages
+            .iter()
+            .find_map(|tag| locale::locale_for_language_tag(tag, country_code))
+            .map(DateTimeFormat::for_locale)
+            .unwrap_or_else(|| DateTimeFormat::for_country(country_code));
+        if let Some(clock) = self.clock {
+            format.clock = clock;
+        }
+        format
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UnitPreference {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(UnitPreference {
+            system: query_or_cookie(req, UNITS),
+            wind_speed: query_or_cookie(req, WIND),
+            pressure: query_or_cookie(req, PRESSURE),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LanguagePreference {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(LanguagePreference(accept_languages(req)))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CountryHints {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(CountryHints {
+            accept_language: req
+                .headers()
+                .get_one("Accept-Language")
+                .and_then(country::from_accept_language),
+            last_search: country_from_cookie(req.cookies()),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DateTimePreference {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(DateTimePreference {
+            clock: query_or_cookie(req, CLOCK),
+            languages: accept_languages(req),
+        })
+    }
+}
+
+fn accept_languages(req: &Request<'_>) -> Vec<String> {
+    req.headers()
+        .get_one("Accept-Language")
+        .map(|header| {
+            locale::parse_accept_language(header)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Read a preference from the query parameter `name`, falling back on the private cookie of the
+/// same name
+///
+/// A valid value in the query parameter is stored in the cookie.
+fn query_or_cookie<T: FromStr>(req: &Request<'_>, name: &'static str) -> Option<T> {
+    let cookies = req.cookies();
+    let query = req
+        .query_value::<&str>(name)
+        .and_then(|value| value.ok())
+        .filter(|value| value.parse::<T>().is_ok());
+    if let Some(value) = query {
+        cookies.add_private(Cookie::build((name, value.to_string())).permanent());
+        return value.parse().ok();
+    }
+
+    cookies
+        .get_private(name)
+        .and_then(|cookie| cookie.value().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn country(code: &str) -> Option<Country> {
+        Country::from_code(code)
+    }
+
+    fn infer(ip: Option<Country>, accept_language: &str, last_search: Option<Country>) -> String {
+        let hints = CountryHints {
+            accept_language: country::from_accept_language(accept_language),
+            last_search,
+        };
+        hints
+            .infer(ip)
+            .map(|country| country.code().to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn ip_country_first() {
+        assert_eq!(infer(country("NZ"), "en-AU", country("GB")), "NZ");
+    }
+
+    #[test]
+    fn accept_language_without_ip_country() {
+        assert_eq!(infer(None, "en-AU,en;q=0.9", country("GB")), "AU");
+        assert_eq!(infer(None, "zh-Hant-TW", None), "TW");
+    }
+
+    #[test]
+    fn last_search_without_other_hints() {
+        assert_eq!(infer(None, "en", country("GB")), "GB");
+        // Numeric regions aren't countries
+        assert_eq!(infer(None, "es-419", country("MX")), "MX");
+    }
+
+    #[test]
+    fn no_hints() {
+        assert_eq!(infer(None, "", None), "");
+    }
+}
@@ -0,0 +1,43 @@
+//! URL slugs for place names, e.g. `brisbane` or `sao-paulo`
+
+/// Turn `name` into a slug: transliterated to lowercase ASCII with runs of other characters
+/// replaced by a single hyphen
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in deunicode::deunicode(name).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accents_are_transliterated() {
+        assert_eq!(slugify("São Paulo"), "sao-paulo");
+        assert_eq!(slugify("Zürich"), "zurich");
+        assert_eq!(slugify("Ōsaka"), "osaka");
+    }
+
+    #[test]
+    fn punctuation_becomes_single_hyphens() {
+        assert_eq!(slugify("St. John's"), "st-john-s");
+        assert_eq!(slugify("Stratford-upon-Avon"), "stratford-upon-avon");
+        assert_eq!(slugify("  (Brisbane) "), "brisbane");
+        assert_eq!(slugify("?!"), "");
+    }
+
+    #[test]
+    fn coordinate_names() {
+        assert_eq!(slugify("27.4700°S 153.0200°E"), "27-4700degs-153-0200dege");
+    }
+}
@@ -3,9 +3,14 @@
 //! https://openweathermap.org/api/one-call-api
 
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-use chrono::{DateTime, FixedOffset, TimeZone, Utc};
-use rocket::serde::Deserialize;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rocket::serde::{Deserialize, Deserializer};
+
+use crate::country;
+use crate::locale::DateTimeFormat;
 
 // Wrapper types with private fields
 
@@ -79,10 +84,49 @@ pub struct Longitude(f32);
 #[serde(crate = "rocket::serde")]
 pub struct TimezoneOffset(i32);
 
+/// An IANA timezone name, resolved against the embedded tz database when deserialised
+///
+/// `None` if the name is unknown to the database.
+#[derive(Copy, Clone)]
+pub struct TimezoneName(Option<Tz>);
+
 #[derive(Deserialize, Copy, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct Millimetres(f32);
 
+// Wrapper types for converted values
+
+#[derive(Copy, Clone)]
+pub struct KilometresPerHour(f32);
+
+#[derive(Copy, Clone)]
+pub struct MilesPerHour(f32);
+
+#[derive(Copy, Clone)]
+pub struct Knots(f32);
+
+/// Beaufort wind force scale, 0..=12
+#[derive(Copy, Clone)]
+pub struct Beaufort(u8);
+
+#[derive(Copy, Clone)]
+pub struct InchesOfMercury(f32);
+
+#[derive(Copy, Clone)]
+pub struct MillimetresOfMercury(f32);
+
+#[derive(Copy, Clone)]
+pub struct Kilometres(f32);
+
+#[derive(Copy, Clone)]
+pub struct Miles(f32);
+
+#[derive(Copy, Clone)]
+pub struct Inches(f32);
+
+#[derive(Copy, Clone)]
+pub struct Feet(f32);
+
 // Public structs composed of wrapper types
 
 #[derive(Deserialize, Clone)]
@@ -90,7 +134,7 @@ pub struct Millimetres(f32);
 pub struct OneCall {
     pub lat: Latitude,
     pub lon: Longitude,
-    pub timezone: String,
+    pub timezone: TimezoneName,
     pub timezone_offset: TimezoneOffset,
     pub current: CurrentWeather,
     pub daily: Vec<DailyForecast>,
@@ -106,7 +150,8 @@ pub struct CurrentWeather {
     pub feels_like: Kelvin,
     pub pressure: HPa,
     pub humidity: Percent,
-    pub dew_point: Kelvin,
+    #[serde(default)]
+    pub dew_point: Option<Kelvin>,
     pub uvi: UVIndex,
     pub clouds: Percent,
     pub visibility: Metres,
@@ -137,7 +182,8 @@ pub struct DailyForecast {
     pub feels_like: FeelsLike,
     pub pressure: HPa,
     pub humidity: Percent,
-    pub dew_point: Kelvin,
+    #[serde(default)]
+    pub dew_point: Option<Kelvin>,
     pub wind_speed: MetresPerSecond,
     pub wind_deg: Degrees,
     pub wind_gust: MetresPerSecond,
@@ -169,12 +215,84 @@ pub struct FeelsLike {
     pub morn: Kelvin,
 }
 
+/// A set of units used to present weather data
+#[derive(Copy, Clone, Debug)]
+pub enum UnitSystem {
+    /// °C, km/h, hPa, km, mm
+    Metric,
+    /// °F, mph, inHg, miles, inches
+    Imperial,
+    /// °C, mph, hPa, miles, mm
+    Uk,
+}
+
+/// The units weather data is presented in: those of a [UnitSystem], except for any wind speed or
+/// pressure unit chosen separately
+#[derive(Copy, Clone, Debug)]
+pub struct Units {
+    pub system: UnitSystem,
+    wind_speed: Option<WindSpeedUnit>,
+    pressure: Option<PressureUnit>,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum TemperatureUnit {
     Celsius,
     Fahrenheit,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WindSpeedUnit {
+    MetresPerSecond,
+    KilometresPerHour,
+    MilesPerHour,
+    Knots,
+    Beaufort,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PressureUnit {
+    HectoPascals,
+    InchesOfMercury,
+    MillimetresOfMercury,
+}
+
+/// How humid it feels, according to the dew point
+///
+/// Bands are from <https://en.wikipedia.org/wiki/Dew_point#Relationship_to_human_comfort>
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Comfort {
+    Dry,
+    VeryComfortable,
+    Comfortable,
+    Noticeable,
+    SomewhatUncomfortable,
+    VeryHumid,
+    Oppressive,
+    Dangerous,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum DistanceUnit {
+    Kilometres,
+    Miles,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PrecipitationUnit {
+    Millimetres,
+    Inches,
+}
+
+/// The timezone of a forecast location
+#[derive(Copy, Clone)]
+pub enum Timezone {
+    /// IANA timezone from the embedded tz database
+    Named(Tz),
+    /// Fixed offset from UTC, captured when the forecast was fetched
+    Fixed(TimezoneOffset),
+}
+
 pub struct Precipitation {
     rain_or_snow: RainOrSnow,
     pub probability: Probability,
@@ -186,57 +304,146 @@ pub enum RainOrSnow {
 }
 
 impl OneCall {
-    pub fn sunrise(&self) -> String {
-        self.current.sunrise.time_12h(&self.timezone_offset)
+    /// The timezone of the location
+    ///
+    /// Uses the IANA timezone so that DST transitions during the forecast period are accounted
+    /// for, falling back on the fixed offset if the name is unknown.
+    pub fn tz(&self) -> Timezone {
+        self.timezone
+            .0
+            .map(Timezone::Named)
+            .unwrap_or(Timezone::Fixed(self.timezone_offset))
     }
 
-    pub fn sunset(&self) -> String {
-        self.current.sunset.time_12h(&self.timezone_offset)
+    pub fn sunrise(&self, format: &DateTimeFormat) -> String {
+        self.current.sunrise.time(&self.tz(), format)
     }
 
-    pub fn current_temp(&self, unit: &TemperatureUnit) -> String {
-        match unit {
-            TemperatureUnit::Celsius => self.current.temp.to_celcius().to_string(),
-            TemperatureUnit::Fahrenheit => self.current.temp.to_fahrenheit().to_string(),
-        }
+    pub fn sunset(&self, format: &DateTimeFormat) -> String {
+        self.current.sunset.time(&self.tz(), format)
     }
 
-    pub fn feels_like(&self, unit: &TemperatureUnit) -> String {
-        match unit {
-            TemperatureUnit::Celsius => self.current.feels_like.to_celcius().to_string(),
-            TemperatureUnit::Fahrenheit => self.current.feels_like.to_fahrenheit().to_string(),
+    pub fn current_temp(&self, units: &Units) -> String {
+        self.current.temp.display(units.temperature())
+    }
+
+    pub fn feels_like(&self, units: &Units) -> String {
+        self.current.feels_like.display(units.temperature())
+    }
+
+    pub fn dew_point(&self, units: &Units) -> String {
+        self.current
+            .dew_point
+            .unwrap_or_else(|| magnus_tetens(self.current.temp, self.current.humidity))
+            .display(units.temperature())
+    }
+
+    pub fn wind(&self, units: &Units) -> String {
+        format!(
+            "{} {}",
+            self.current.wind_speed.display(units.wind_speed()),
+            self.current.wind_deg.compass_point()
+        )
+    }
+
+    pub fn pressure(&self, units: &Units) -> String {
+        self.current.pressure.display(units.pressure())
+    }
+
+    pub fn visibility(&self, units: &Units) -> String {
+        self.current.visibility.display(units.distance())
+    }
+
+    /// Compare upstream dew point values against our own calculation
+    ///
+    /// A warning is logged for each value that differs by more than `tolerance` degrees.
+    pub fn check_dew_points(&self, tolerance: f32) {
+        let current = &self.current;
+        self.check_dew_point(
+            current.dt,
+            current.dew_point,
+            current.temp,
+            current.humidity,
+            tolerance,
+        );
+        for day in &self.daily {
+            self.check_dew_point(day.dt, day.dew_point, day.temp.day, day.humidity, tolerance);
         }
     }
 
-    pub fn dew_point(&self, unit: &TemperatureUnit) -> String {
-        match unit {
-            TemperatureUnit::Celsius => self.current.dew_point.to_celcius().to_string(),
-            TemperatureUnit::Fahrenheit => self.current.dew_point.to_fahrenheit().to_string(),
+    fn check_dew_point(
+        &self,
+        dt: UnixTimestamp,
+        upstream: Option<Kelvin>,
+        temp: Kelvin,
+        humidity: Percent,
+        tolerance: f32,
+    ) {
+        let upstream = match upstream {
+            Some(upstream) => upstream,
+            None => return,
+        };
+        if let Some(computed) = divergent_dew_point(upstream, temp, humidity, tolerance) {
+            warn!(
+                "Dew point for {},{} at {} differs from computed value: upstream {}, computed {}",
+                self.lat.0,
+                self.lon.0,
+                dt.to_chrono(),
+                upstream.to_celcius(),
+                computed.to_celcius()
+            );
         }
     }
 }
 
+/// Our own dew point for `temp` and `humidity`, if it differs from `upstream` by more than
+/// `tolerance` degrees
+fn divergent_dew_point(
+    upstream: Kelvin,
+    temp: Kelvin,
+    humidity: Percent,
+    tolerance: f32,
+) -> Option<Kelvin> {
+    let computed = magnus_tetens(temp, humidity);
+    ((computed.0 - upstream.0).abs() > tolerance).then_some(computed)
+}
+
 impl DailyForecast {
-    pub fn dew_point(&self, unit: &TemperatureUnit) -> String {
-        match unit {
-            TemperatureUnit::Celsius => self.dew_point.to_celcius().to_string(),
-            TemperatureUnit::Fahrenheit => self.dew_point.to_fahrenheit().to_string(),
-        }
+    pub fn dew_point(&self, units: &Units) -> String {
+        self.dew_point_kelvin().display(units.temperature())
     }
 
-    pub fn max(&self, unit: &TemperatureUnit) -> String {
-        match unit {
-            TemperatureUnit::Celsius => self.temp.max.to_celcius().to_string(),
-            TemperatureUnit::Fahrenheit => self.temp.max.to_fahrenheit().to_string(),
-        }
+    pub fn comfort(&self) -> Comfort {
+        Comfort::from_dew_point(self.dew_point_kelvin())
+    }
+
+    fn dew_point_kelvin(&self) -> Kelvin {
+        self.dew_point
+            .unwrap_or_else(|| magnus_tetens(self.temp.day, self.humidity))
+    }
+
+    pub fn max(&self, units: &Units) -> String {
+        self.temp.max.display(units.temperature())
+    }
+
+    pub fn wind(&self, units: &Units) -> String {
+        format!(
+            "{} {}",
+            self.wind_speed.display(units.wind_speed()),
+            self.wind_deg.compass_point()
+        )
     }
 
-    pub fn sunrise(&self, timezone_offset: &TimezoneOffset) -> String {
-        self.sunrise.time_12h(timezone_offset)
+    pub fn pressure(&self, units: &Units) -> String {
+        self.pressure.display(units.pressure())
     }
 
-    pub fn sunset(&self, timezone_offset: &TimezoneOffset) -> String {
-        self.sunset.time_12h(timezone_offset)
+    pub fn sunrise(&self, tz: &Timezone, format: &DateTimeFormat) -> String {
+        self.sunrise.time(tz, format)
+    }
+
+    pub fn sunset(&self, tz: &Timezone, format: &DateTimeFormat) -> String {
+        self.sunset.time(tz, format)
     }
 
     pub fn precipitation(&self) -> Option<Precipitation> {
@@ -256,6 +463,237 @@ impl DailyForecast {
     }
 }
 
+impl Comfort {
+    pub fn from_dew_point(dew_point: Kelvin) -> Self {
+        let celsius = dew_point.to_celcius().0;
+        match celsius {
+            c if c < 10. => Comfort::Dry,
+            c if c < 13. => Comfort::VeryComfortable,
+            c if c < 16. => Comfort::Comfortable,
+            c if c < 18. => Comfort::Noticeable,
+            c if c < 21. => Comfort::SomewhatUncomfortable,
+            c if c < 24. => Comfort::VeryHumid,
+            c if c < 26. => Comfort::Oppressive,
+            _ => Comfort::Dangerous,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Comfort::Dry => "A bit dry",
+            Comfort::VeryComfortable => "Very comfortable",
+            Comfort::Comfortable => "Comfortable",
+            Comfort::Noticeable => "Humidity noticeable",
+            Comfort::SomewhatUncomfortable => "Somewhat uncomfortable",
+            Comfort::VeryHumid => "Very humid",
+            Comfort::Oppressive => "Oppressive",
+            Comfort::Dangerous => "Dangerously humid",
+        }
+    }
+}
+
+impl UnitSystem {
+    /// The default unit system for a country, identified by its ISO 3166-1 alpha-2 code
+    pub fn for_country(code: &str) -> Self {
+        country::info(code)
+            .map(|info| info.units)
+            .unwrap_or(UnitSystem::Metric)
+    }
+
+    /// All unit systems, in the order they are presented to the user
+    pub fn all() -> [UnitSystem; 3] {
+        [UnitSystem::Metric, UnitSystem::Imperial, UnitSystem::Uk]
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "metric",
+            UnitSystem::Imperial => "imperial",
+            UnitSystem::Uk => "uk",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "Metric",
+            UnitSystem::Imperial => "Imperial",
+            UnitSystem::Uk => "UK",
+        }
+    }
+
+    pub fn temperature(self) -> TemperatureUnit {
+        match self {
+            UnitSystem::Metric | UnitSystem::Uk => TemperatureUnit::Celsius,
+            UnitSystem::Imperial => TemperatureUnit::Fahrenheit,
+        }
+    }
+
+    pub fn wind_speed(self) -> WindSpeedUnit {
+        match self {
+            UnitSystem::Metric => WindSpeedUnit::KilometresPerHour,
+            UnitSystem::Imperial | UnitSystem::Uk => WindSpeedUnit::MilesPerHour,
+        }
+    }
+
+    pub fn pressure(self) -> PressureUnit {
+        match self {
+            UnitSystem::Metric | UnitSystem::Uk => PressureUnit::HectoPascals,
+            UnitSystem::Imperial => PressureUnit::InchesOfMercury,
+        }
+    }
+
+    pub fn distance(self) -> DistanceUnit {
+        match self {
+            UnitSystem::Metric => DistanceUnit::Kilometres,
+            UnitSystem::Imperial | UnitSystem::Uk => DistanceUnit::Miles,
+        }
+    }
+
+    pub fn precipitation(self) -> PrecipitationUnit {
+        match self {
+            UnitSystem::Metric | UnitSystem::Uk => PrecipitationUnit::Millimetres,
+            UnitSystem::Imperial => PrecipitationUnit::Inches,
+        }
+    }
+}
+
+impl Units {
+    pub fn new(
+        system: UnitSystem,
+        wind_speed: Option<WindSpeedUnit>,
+        pressure: Option<PressureUnit>,
+    ) -> Self {
+        Units {
+            system,
+            wind_speed,
+            pressure,
+        }
+    }
+
+    pub fn temperature(self) -> TemperatureUnit {
+        self.system.temperature()
+    }
+
+    pub fn wind_speed(self) -> WindSpeedUnit {
+        self.wind_speed.unwrap_or_else(|| self.system.wind_speed())
+    }
+
+    pub fn pressure(self) -> PressureUnit {
+        self.pressure.unwrap_or_else(|| self.system.pressure())
+    }
+
+    pub fn distance(self) -> DistanceUnit {
+        self.system.distance()
+    }
+
+    pub fn precipitation(self) -> PrecipitationUnit {
+        self.system.precipitation()
+    }
+}
+
+impl From<UnitSystem> for Units {
+    fn from(system: UnitSystem) -> Self {
+        Units::new(system, None, None)
+    }
+}
+
+impl WindSpeedUnit {
+    /// All wind speed units, in the order they are presented to the user
+    pub fn all() -> [WindSpeedUnit; 5] {
+        [
+            WindSpeedUnit::KilometresPerHour,
+            WindSpeedUnit::MetresPerSecond,
+            WindSpeedUnit::MilesPerHour,
+            WindSpeedUnit::Knots,
+            WindSpeedUnit::Beaufort,
+        ]
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WindSpeedUnit::MetresPerSecond => "mps",
+            WindSpeedUnit::KilometresPerHour => "kmh",
+            WindSpeedUnit::MilesPerHour => "mph",
+            WindSpeedUnit::Knots => "knots",
+            WindSpeedUnit::Beaufort => "beaufort",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WindSpeedUnit::MetresPerSecond => "m/s",
+            WindSpeedUnit::KilometresPerHour => "km/h",
+            WindSpeedUnit::MilesPerHour => "mph",
+            WindSpeedUnit::Knots => "kn",
+            WindSpeedUnit::Beaufort => "Beaufort",
+        }
+    }
+}
+
+impl FromStr for WindSpeedUnit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WindSpeedUnit::all()
+            .iter()
+            .copied()
+            .find(|unit| unit.as_str() == s)
+            .ok_or(())
+    }
+}
+
+impl PressureUnit {
+    /// All pressure units, in the order they are presented to the user
+    pub fn all() -> [PressureUnit; 3] {
+        [
+            PressureUnit::HectoPascals,
+            PressureUnit::InchesOfMercury,
+            PressureUnit::MillimetresOfMercury,
+        ]
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PressureUnit::HectoPascals => "hpa",
+            PressureUnit::InchesOfMercury => "inhg",
+            PressureUnit::MillimetresOfMercury => "mmhg",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PressureUnit::HectoPascals => "hPa",
+            PressureUnit::InchesOfMercury => "inHg",
+            PressureUnit::MillimetresOfMercury => "mmHg",
+        }
+    }
+}
+
+impl FromStr for PressureUnit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PressureUnit::all()
+            .iter()
+            .copied()
+            .find(|unit| unit.as_str() == s)
+            .ok_or(())
+    }
+}
+
+impl FromStr for UnitSystem {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metric" => Ok(UnitSystem::Metric),
+            "imperial" => Ok(UnitSystem::Imperial),
+            "uk" => Ok(UnitSystem::Uk),
+            _ => Err(()),
+        }
+    }
+}
+
 impl Kelvin {
     pub fn to_celcius(self) -> Celsius {
         Celsius(self.0 - 273.15)
@@ -264,26 +702,211 @@ impl Kelvin {
     pub fn to_fahrenheit(self) -> Fahrenheit {
         Fahrenheit(self.to_celcius().0 * 1.8 + 32.0)
     }
+
+    pub fn display(self, unit: TemperatureUnit) -> String {
+        match unit {
+            TemperatureUnit::Celsius => self.to_celcius().to_string(),
+            TemperatureUnit::Fahrenheit => self.to_fahrenheit().to_string(),
+        }
+    }
+}
+
+impl MetresPerSecond {
+    pub fn to_kilometres_per_hour(self) -> KilometresPerHour {
+        KilometresPerHour(self.0 * 3.6)
+    }
+
+    pub fn to_miles_per_hour(self) -> MilesPerHour {
+        MilesPerHour(self.0 * 3600. / 1609.344)
+    }
+
+    pub fn to_knots(self) -> Knots {
+        Knots(self.0 * 3600. / 1852.)
+    }
+
+    pub fn to_beaufort(self) -> Beaufort {
+        // Upper bound (exclusive) of each force in m/s
+        // https://en.wikipedia.org/wiki/Beaufort_scale
+        const LIMITS: [f32; 12] = [
+            0.5, 1.6, 3.4, 5.5, 8.0, 10.8, 13.9, 17.2, 20.8, 24.5, 28.5, 32.7,
+        ];
+        let force = LIMITS.iter().take_while(|&&limit| self.0 >= limit).count();
+        Beaufort(force as u8)
+    }
+
+    pub fn display(self, unit: WindSpeedUnit) -> String {
+        match unit {
+            WindSpeedUnit::MetresPerSecond => self.to_string(),
+            WindSpeedUnit::KilometresPerHour => self.to_kilometres_per_hour().to_string(),
+            WindSpeedUnit::MilesPerHour => self.to_miles_per_hour().to_string(),
+            WindSpeedUnit::Knots => self.to_knots().to_string(),
+            WindSpeedUnit::Beaufort => self.to_beaufort().to_string(),
+        }
+    }
+}
+
+impl HPa {
+    pub fn to_inches_of_mercury(self) -> InchesOfMercury {
+        InchesOfMercury(self.0 as f32 * 0.029_53)
+    }
+
+    pub fn to_millimetres_of_mercury(self) -> MillimetresOfMercury {
+        MillimetresOfMercury(self.0 as f32 * 0.750_062)
+    }
+
+    pub fn display(self, unit: PressureUnit) -> String {
+        match unit {
+            PressureUnit::HectoPascals => self.to_string(),
+            PressureUnit::InchesOfMercury => self.to_inches_of_mercury().to_string(),
+            PressureUnit::MillimetresOfMercury => self.to_millimetres_of_mercury().to_string(),
+        }
+    }
+}
+
+impl Metres {
+    pub fn new(metres: f32) -> Self {
+        Metres(metres)
+    }
+
+    pub fn to_kilometres(self) -> Kilometres {
+        Kilometres(self.0 / 1000.)
+    }
+
+    pub fn to_miles(self) -> Miles {
+        Miles(self.0 / 1609.344)
+    }
+
+    pub fn display(self, unit: DistanceUnit) -> String {
+        match unit {
+            DistanceUnit::Kilometres => self.to_kilometres().to_string(),
+            DistanceUnit::Miles => self.to_miles().to_string(),
+        }
+    }
+
+    pub fn to_feet(self) -> Feet {
+        Feet(self.0 / 0.3048)
+    }
+
+    /// Display a height, such as an elevation, in metres or feet
+    pub fn display_height(self, unit: DistanceUnit) -> String {
+        match unit {
+            DistanceUnit::Kilometres => format!("{:.0} m", self.0),
+            DistanceUnit::Miles => self.to_feet().to_string(),
+        }
+    }
+}
+
+impl Millimetres {
+    pub fn to_inches(self) -> Inches {
+        Inches(self.0 / 25.4)
+    }
+
+    pub fn display(self, unit: PrecipitationUnit) -> String {
+        match unit {
+            PrecipitationUnit::Millimetres => self.to_string(),
+            PrecipitationUnit::Inches => self.to_inches().to_string(),
+        }
+    }
+}
+
+impl Degrees {
+    /// The 16-wind compass point for this direction
+    pub fn compass_point(self) -> &'static str {
+        const POINTS: [&str; 16] = [
+            "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+            "NW", "NNW",
+        ];
+        let index = ((f32::from(self.0 % 360) / 22.5).round() as usize) % POINTS.len();
+        POINTS[index]
+    }
+}
+
+/// Calculate the dew point using the Magnus-Tetens approximation
+///
+/// Uses the constants from Sonntag (1990), which are accurate to within 0.35°C for temperatures
+/// between -45°C and 60°C.
+///
+/// <https://en.wikipedia.org/wiki/Dew_point#Calculating_the_dew_point>
+pub fn magnus_tetens(temp: Kelvin, humidity: Percent) -> Kelvin {
+    const A: f32 = 17.62;
+    const B: f32 = 243.12; // °C
+
+    let t = temp.to_celcius().0;
+    let gamma = humidity.ln_fraction() + (A * t) / (B + t);
+    Celsius(B * gamma / (A - gamma)).to_kelvin()
+}
+
+/// Calculate the dew point using the Arden Buck equation
+///
+/// This is more accurate than [magnus_tetens] at the expense of an extra `exp` call.
+///
+/// <https://en.wikipedia.org/wiki/Arden_Buck_equation>
+pub fn arden_buck(temp: Kelvin, humidity: Percent) -> Kelvin {
+    const B: f32 = 18.678;
+    const C: f32 = 257.14; // °C
+    const D: f32 = 234.5; // °C
+
+    let t = temp.to_celcius().0;
+    let gamma = humidity.ln_fraction() + (B - t / D) * (t / (C + t));
+    Celsius(C * gamma / (B - gamma)).to_kelvin()
+}
+
+impl Celsius {
+    pub fn to_kelvin(self) -> Kelvin {
+        Kelvin(self.0 + 273.15)
+    }
+}
+
+impl Percent {
+    /// Natural log of the percentage as a fraction, clamped to avoid `ln(0)`
+    fn ln_fraction(self) -> f32 {
+        (f32::from(self.0.max(1)) / 100.).ln()
+    }
 }
 
 impl UnixTimestamp {
-    pub fn day_date(self, tz_offset: &TimezoneOffset) -> String {
-        self.in_timezone(*tz_offset)
-            .format("%A, %-d %B")
+    pub fn day_date(self, tz: &Timezone, format: &DateTimeFormat) -> String {
+        self.in_timezone(*tz)
+            .format_localized(format.date_pattern(), format.locale)
             .to_string()
     }
 
-    pub fn time_12h(self, tz_offset: &TimezoneOffset) -> String {
-        self.in_timezone(*tz_offset).format("%I:%M %p").to_string()
+    /// The date at this time in `tz`
+    pub fn local_date(self, tz: &Timezone) -> NaiveDate {
+        self.in_timezone(*tz).date_naive()
+    }
+
+    pub fn time(self, tz: &Timezone, format: &DateTimeFormat) -> String {
+        self.in_timezone(*tz)
+            .format_localized(format.time_pattern(), format.locale)
+            .to_string()
     }
 
     pub fn to_chrono(self) -> DateTime<Utc> {
         Utc.timestamp_opt(self.0, 0).unwrap()
     }
 
-    fn in_timezone(self, tz_offset: TimezoneOffset) -> DateTime<FixedOffset> {
-        self.to_chrono()
-            .with_timezone(&FixedOffset::east_opt(tz_offset.0).unwrap())
+    fn in_timezone(self, tz: Timezone) -> DateTime<FixedOffset> {
+        match tz {
+            Timezone::Named(tz) => self.to_chrono().with_timezone(&tz).fixed_offset(),
+            Timezone::Fixed(tz_offset) => self
+                .to_chrono()
+                .with_timezone(&FixedOffset::east_opt(tz_offset.0).unwrap()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TimezoneName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        let tz = name
+            .parse::<Tz>()
+            .map_err(|_err| warn!("Unknown timezone {}, using the fixed UTC offset", name))
+            .ok();
+        Ok(TimezoneName(tz))
     }
 }
 
@@ -302,11 +925,12 @@ impl Precipitation {
         }
     }
 
-    pub fn volume(&self) -> Millimetres {
+    pub fn volume(&self, units: &Units) -> String {
         match self.rain_or_snow {
             RainOrSnow::Rain(mm) => mm,
             RainOrSnow::Snow(mm) => mm,
         }
+        .display(units.precipitation())
     }
 
     pub fn probability(&self) -> Probability {
@@ -338,6 +962,78 @@ impl Display for Millimetres {
     }
 }
 
+impl Display for Inches {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}in", self.0)
+    }
+}
+
+impl Display for MetresPerSecond {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} m/s", self.0)
+    }
+}
+
+impl Display for KilometresPerHour {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} km/h", self.0)
+    }
+}
+
+impl Display for MilesPerHour {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} mph", self.0)
+    }
+}
+
+impl Display for Knots {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} kn", self.0)
+    }
+}
+
+impl Display for Beaufort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Force {}", self.0)
+    }
+}
+
+impl Display for HPa {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} hPa", self.0)
+    }
+}
+
+impl Display for InchesOfMercury {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} inHg", self.0)
+    }
+}
+
+impl Display for MillimetresOfMercury {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} mmHg", self.0)
+    }
+}
+
+impl Display for Kilometres {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} km", self.0)
+    }
+}
+
+impl Display for Miles {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} mi", self.0)
+    }
+}
+
+impl Display for Feet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} ft", self.0)
+    }
+}
+
 impl Display for Probability {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}%", (self.0 * 100.).round())
@@ -349,3 +1045,271 @@ impl Display for UVIndex {
         write!(f, "{:.1}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Clock;
+    use rocket::serde::json;
+
+    fn celsius(value: f32) -> Kelvin {
+        Celsius(value).to_kelvin()
+    }
+
+    fn assert_close(actual: Kelvin, expected_celsius: f32, tolerance: f32) {
+        let actual = actual.to_celcius().0;
+        assert!(
+            (actual - expected_celsius).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected_celsius
+        );
+    }
+
+    // Reference values from the NOAA dew point calculator
+    // https://www.wpc.ncep.noaa.gov/html/dewrh.shtml
+    const REFERENCE: [(f32, u8, f32); 5] = [
+        (20., 50, 9.3),
+        (30., 70, 23.9),
+        (25., 100, 25.0),
+        (0., 80, -3.0),
+        (-10., 60, -16.4),
+    ];
+
+    #[test]
+    fn magnus_tetens_matches_reference_values() {
+        for (temp, humidity, dew_point) in REFERENCE {
+            assert_close(
+                magnus_tetens(celsius(temp), Percent(humidity)),
+                dew_point,
+                0.35,
+            );
+        }
+    }
+
+    #[test]
+    fn arden_buck_matches_reference_values() {
+        for (temp, humidity, dew_point) in REFERENCE {
+            assert_close(arden_buck(celsius(temp), Percent(humidity)), dew_point, 0.2);
+        }
+    }
+
+    #[test]
+    fn zero_humidity_is_finite() {
+        assert!(magnus_tetens(celsius(20.), Percent(0)).0.is_finite());
+        assert!(arden_buck(celsius(20.), Percent(0)).0.is_finite());
+    }
+
+    #[test]
+    fn dew_point_within_tolerance_is_not_divergent() {
+        let upstream = celsius(9.5);
+        assert!(divergent_dew_point(upstream, celsius(20.), Percent(50), 1.0).is_none());
+    }
+
+    #[test]
+    fn dew_point_outside_tolerance_is_divergent() {
+        let upstream = celsius(12.);
+        let computed = divergent_dew_point(upstream, celsius(20.), Percent(50), 1.0)
+            .expect("dew point should diverge");
+        assert_close(computed, 9.3, 0.1);
+    }
+
+    fn assert_approx(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn timestamp(year: i32, month: u32, day: u32, hour: u32, min: u32) -> UnixTimestamp {
+        UnixTimestamp(
+            Utc.with_ymd_and_hms(year, month, day, hour, min, 0)
+                .unwrap()
+                .timestamp(),
+        )
+    }
+
+    fn named(name: &str) -> Timezone {
+        let name = json::to_string(&name).unwrap();
+        Timezone::Named(json::from_str::<TimezoneName>(&name).unwrap().0.unwrap())
+    }
+
+    const AU_FORMAT: DateTimeFormat = DateTimeFormat {
+        locale: chrono::Locale::en_AU,
+        clock: Clock::TwentyFourHour,
+    };
+
+    const US_FORMAT: DateTimeFormat = DateTimeFormat {
+        locale: chrono::Locale::en_US,
+        clock: Clock::TwelveHour,
+    };
+
+    #[test]
+    fn unknown_timezone_name() {
+        assert!(json::from_str::<TimezoneName>("\"Mars/Olympus_Mons\"")
+            .unwrap()
+            .0
+            .is_none());
+    }
+
+    #[test]
+    fn sydney_sunrise_across_end_of_dst() {
+        // DST ends at 03:00 AEDT on Sunday 6 April 2025 (16:00 UTC on the 5th)
+        let tz = named("Australia/Sydney");
+        let before = timestamp(2025, 4, 4, 19, 26);
+        let after = timestamp(2025, 4, 5, 19, 27);
+        assert_eq!(before.time(&tz, &AU_FORMAT), "06:26");
+        assert_eq!(after.time(&tz, &AU_FORMAT), "05:27");
+
+        // The fixed offset captured before the transition gets the later day wrong
+        let fixed = Timezone::Fixed(TimezoneOffset(11 * 60 * 60));
+        assert_eq!(after.time(&fixed, &AU_FORMAT), "06:27");
+    }
+
+    #[test]
+    fn sydney_dates_across_end_of_dst() {
+        let tz = named("Australia/Sydney");
+        // 00:30 AEDT, still before the transition
+        assert_eq!(
+            timestamp(2025, 4, 5, 13, 30).day_date(&tz, &AU_FORMAT),
+            "Sunday, 6 April"
+        );
+        // 23:30 AEST, after it
+        assert_eq!(
+            timestamp(2025, 4, 6, 13, 30).day_date(&tz, &AU_FORMAT),
+            "Sunday, 6 April"
+        );
+        assert_eq!(
+            timestamp(2025, 4, 6, 14, 30).day_date(&tz, &AU_FORMAT),
+            "Monday, 7 April"
+        );
+    }
+
+    #[test]
+    fn new_york_across_start_of_dst() {
+        // DST starts at 02:00 EST on Sunday 9 March 2025 (07:00 UTC)
+        let tz = named("America/New_York");
+        let before = timestamp(2025, 3, 8, 11, 26);
+        let after = timestamp(2025, 3, 9, 11, 25);
+        assert_eq!(before.time(&tz, &US_FORMAT), "06:26 AM");
+        assert_eq!(after.time(&tz, &US_FORMAT), "07:25 AM");
+        assert_eq!(before.day_date(&tz, &US_FORMAT), "Saturday, March 8");
+        assert_eq!(after.day_date(&tz, &US_FORMAT), "Sunday, March 9");
+        // 23:30 EDT on the 9th
+        assert_eq!(
+            timestamp(2025, 3, 10, 3, 30).day_date(&tz, &US_FORMAT),
+            "Sunday, March 9"
+        );
+    }
+
+    #[test]
+    fn local_dates_differ_by_timezone() {
+        // Midday in Brisbane is 3am in London
+        let dt = timestamp(2025, 7, 1, 2, 0);
+        assert_eq!(
+            dt.local_date(&named("Australia/Brisbane")),
+            NaiveDate::from_ymd_opt(2025, 7, 1).unwrap()
+        );
+        assert_eq!(
+            dt.local_date(&named("America/New_York")),
+            NaiveDate::from_ymd_opt(2025, 6, 30).unwrap()
+        );
+    }
+
+    #[test]
+    fn temperature_conversions() {
+        assert_approx(Kelvin(273.15).to_celcius().0, 0., 0.001);
+        assert_approx(Kelvin(273.15).to_fahrenheit().0, 32., 0.001);
+        assert_approx(celsius(100.).to_fahrenheit().0, 212., 0.01);
+        assert_approx(celsius(-40.).to_fahrenheit().0, -40., 0.01);
+        assert_approx(Celsius(20.).to_kelvin().0, 293.15, 0.001);
+    }
+
+    #[test]
+    fn wind_speed_conversions() {
+        let speed = MetresPerSecond(10.);
+        assert_approx(speed.to_kilometres_per_hour().0, 36., 0.001);
+        assert_approx(speed.to_miles_per_hour().0, 22.369, 0.001);
+        assert_approx(MetresPerSecond(0.44704).to_miles_per_hour().0, 1., 0.001);
+        assert_approx(speed.to_knots().0, 19.438, 0.001);
+        assert_approx(MetresPerSecond(1852. / 3600.).to_knots().0, 1., 0.001);
+    }
+
+    #[test]
+    fn beaufort_scale() {
+        let force = |speed| MetresPerSecond(speed).to_beaufort().0;
+        assert_eq!(force(0.), 0);
+        assert_eq!(force(0.49), 0);
+        assert_eq!(force(0.5), 1);
+        assert_eq!(force(10.), 5);
+        assert_eq!(force(10.8), 6);
+        assert_eq!(force(32.6), 11);
+        assert_eq!(force(32.7), 12);
+        assert_eq!(force(70.), 12);
+    }
+
+    #[test]
+    fn pressure_conversions() {
+        assert_approx(HPa(1013).to_inches_of_mercury().0, 29.91, 0.01);
+        assert_approx(HPa(1000).to_inches_of_mercury().0, 29.53, 0.01);
+        assert_approx(HPa(1013).to_millimetres_of_mercury().0, 759.81, 0.01);
+    }
+
+    #[test]
+    fn distance_conversions() {
+        assert_approx(Metres(1609.344).to_miles().0, 1., 0.001);
+        assert_approx(Metres(1500.).to_kilometres().0, 1.5, 0.001);
+        assert_approx(Metres(0.3048).to_feet().0, 1., 0.001);
+        assert_approx(Millimetres(25.4).to_inches().0, 1., 0.001);
+    }
+
+    #[test]
+    fn displayed_units_follow_unit_system() {
+        let temp = celsius(20.);
+        assert_eq!(temp.display(UnitSystem::Metric.temperature()), "20.0°C");
+        assert_eq!(temp.display(UnitSystem::Imperial.temperature()), "68.0°F");
+        let wind = MetresPerSecond(10.);
+        assert_eq!(wind.display(UnitSystem::Metric.wind_speed()), "36 km/h");
+        assert_eq!(wind.display(UnitSystem::Uk.wind_speed()), "22 mph");
+        assert_eq!(HPa(1013).display(UnitSystem::Uk.pressure()), "1013 hPa");
+        assert_eq!(
+            HPa(1013).display(UnitSystem::Imperial.pressure()),
+            "29.91 inHg"
+        );
+    }
+
+    #[test]
+    fn chosen_units_override_unit_system() {
+        let units = Units::new(
+            UnitSystem::Metric,
+            Some(WindSpeedUnit::Knots),
+            Some(PressureUnit::MillimetresOfMercury),
+        );
+        assert_eq!(MetresPerSecond(10.).display(units.wind_speed()), "19 kn");
+        assert_eq!(HPa(1013).display(units.pressure()), "760 mmHg");
+        assert_eq!(celsius(20.).display(units.temperature()), "20.0°C");
+
+        let units = Units::new(UnitSystem::Uk, Some(WindSpeedUnit::Beaufort), None);
+        assert_eq!(MetresPerSecond(10.).display(units.wind_speed()), "Force 5");
+        assert_eq!(units.pressure(), PressureUnit::HectoPascals);
+        assert_eq!(
+            Units::from(UnitSystem::Imperial).wind_speed(),
+            WindSpeedUnit::MilesPerHour
+        );
+    }
+
+    #[test]
+    fn units_parse_from_their_names() {
+        for unit in WindSpeedUnit::all() {
+            assert_eq!(unit.as_str().parse(), Ok(unit));
+        }
+        for unit in PressureUnit::all() {
+            assert_eq!(unit.as_str().parse(), Ok(unit));
+        }
+        assert!("furlongs".parse::<WindSpeedUnit>().is_err());
+    }
+}